@@ -1,61 +1,197 @@
 
+use crate::merge::MergeRule;
+use crate::engine::Lines;
+use crate::zobrist;
 
-pub fn slide_right(data: &[i32]) -> (Vec<i32>, i32, bool) {
-    let mut row: Vec<i32> = data.iter().copied().filter(|&x| x != 0).collect(); // Remove zeros
-    let mut score = 0;
-    let mut is_moving = false;
-
-    let mut i = row.len();
-    
-    while i > 1 {
-        if row[i - 1] == row[i - 2] { // If adjacent tiles are equal, merge them
-            row[i - 1] += 1;
-            score += row[i - 1]; // Add to score
-            row[i - 2] = 0; // Clear merged tile
-            i -= 1; // Skip the next tile to prevent double merging
-        }
-        i -= 1;
-    }
+/// Where one tile ended up after a slide, like `previousPosition`/`mergedFrom`
+/// in the JavaScript version. Positions are cells of the board.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TileMove {
+    pub from: usize,
+    pub to: usize,
+    // The position before the move of the tile it merged with
+    pub merged_with: Option<usize>,
+    // The tile at `to` after the move
+    pub tile: i32,
+}
 
-    let mut result: Vec<i32> = row.into_iter().filter(|&x| x != 0).collect(); // Remove extra zeros
-    while result.len() < data.len() {
-        is_moving = true;
-        result.insert(0, 0); // Fill empty spaces with zeros on the left
+impl TileMove {
+    /// Whether the tile changed place or merged.
+    pub fn is_moving(&self) -> bool {
+        self.from != self.to || self.merged_with.is_some()
     }
 
-    (result, score, is_moving)
+    /// The same move with its positions mapped, e.g. from cells of a line to cells of the board.
+    pub fn map<F: Fn(usize) -> usize>(self, cell: F) -> Self {
+        TileMove {
+            from: cell(self.from),
+            to: cell(self.to),
+            merged_with: self.merged_with.map(&cell),
+            tile: self.tile,
+        }
+    }
 }
 
+/// What sliding one line changed, see `slide_line`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LineSlide {
+    // Sum of the values of the tiles made by merges, the standard 2048 score
+    pub score: u64,
+    // Sum of the exponents of the tiles made by merges, kept as a secondary metric
+    pub exponent_score: i32,
+    // The most valuable tile made by a merge, 0 without merges
+    pub max_merged: i32,
+    pub is_moving: bool,
+}
 
+/// Slides a line of the board in place: the one of `lines` starting at `start`, towards its first cell
+/// when `towards_start` is set and towards the last one otherwise. Adds to `trace`, when given,
+/// where every tile of the new line came from, in cells of `tiles`. Keeps `hash`, the Zobrist hash
+/// of the board, up to date with the cells it changes.
+pub fn slide_line(
+    tiles: &mut [u8],
+    hash: &mut u64,
+    lines: Lines,
+    start: usize,
+    towards_start: bool,
+    rule: &dyn MergeRule,
+    mut trace: Option<&mut Vec<TileMove>>,
+) -> LineSlide {
+    let Lines { length, stride, .. } = lines;
+    // The cells of the line from the wall outwards
+    let cell = |t: usize| if towards_start { start + t * stride } else { start + (length - 1 - t) * stride };
+    let mut slide = LineSlide::default();
+    // Where the next tile goes, and whether the one before it can still merge
+    let mut to = 0;
+    let mut open = false;
+    for t in 0..length {
+        let from = cell(t);
+        let tile = tiles[from];
+        if tile == 0 {
+            continue;
+        }
+        zobrist::set(tiles, hash, from, 0);
+        // If the rule lets the tile merge with the last one placed, merge them, once
+        let merge = if open { rule.merge(tiles[cell(to - 1)] as i32, tile as i32) } else { None };
+        match merge {
+            Some(merged) => {
+                zobrist::set(tiles, hash, cell(to - 1), merged as u8);
+                slide.score = slide.score.saturating_add(rule.value(merged)); // Add to score
+                slide.exponent_score += merged;
+                if rule.value(merged) > rule.value(slide.max_merged) {
+                    slide.max_merged = merged;
+                }
+                slide.is_moving = true;
+                if let Some(last) = trace.as_deref_mut().and_then(|trace| trace.last_mut()) {
+                    last.merged_with = Some(from);
+                    last.tile = merged;
+                }
+                open = false;
+            }
+            None => {
+                zobrist::set(tiles, hash, cell(to), tile);
+                slide.is_moving |= cell(to) != from;
+                if let Some(trace) = trace.as_deref_mut() {
+                    trace.push(TileMove { from, to: cell(to), merged_with: None, tile: tile as i32 });
+                }
+                to += 1;
+                open = true;
+            }
+        }
+    }
+    slide
+}
 
+// Whether sliding a line towards its wall changes it, the tiles given from the wall outwards.
+fn can_slide_towards_wall<I: Iterator<Item = i32>>(tiles: I, rule: &dyn MergeRule) -> bool {
+    let mut gap = false;
+    let mut previous = 0;
+    for tile in tiles {
+        if tile == 0 {
+            gap = true;
+            continue;
+        }
+        // A tile with an empty cell between it and the wall slides
+        if gap {
+            return true;
+        }
+        if previous != 0 && rule.merge(previous, tile).is_some() {
+            return true;
+        }
+        previous = tile;
+    }
+    false
+}
 
-pub fn slide_left(data: &[i32]) -> (Vec<i32>, i32, bool) {
-    let reversed: Vec<i32> = data.iter().rev().copied().collect();
-    let (mut new_row, score, is_moving) = slide_right(&reversed);
-    new_row.reverse();
-    (new_row, score, is_moving)
+/// Whether sliding any line of `data` along an axis changes it.
+/// The lines have `n` cells `stride` apart, and slide towards their first cell when `towards_start` is set.
+pub fn can_slide_along(data: &[u8], n: usize, stride: usize, towards_start: bool, rule: &dyn MergeRule) -> bool {
+    (0..data.len())
+        .filter(|&start| (start / stride).is_multiple_of(n))
+        .any(|start| {
+            let line = (0..n).map(|t| data[start + t * stride] as i32);
+            if towards_start {
+                can_slide_towards_wall(line, rule)
+            } else {
+                can_slide_towards_wall(line.rev(), rule)
+            }
+        })
 }
 
+/// The cells of a `rows` x `columns` board read column by column, a `columns` x `rows` board.
+pub fn transpose_rectangle(data: &[u8], rows: usize, columns: usize) -> Vec<u8> {
+    (0..columns)
+        .flat_map(|c| (0..rows).map(move |r| data[r * columns + c]))
+        .collect()
+}
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merge::PowersOfTwo;
 
+    // Slides the line starting at `start` of a board of `shape`, with its trace
+    fn slide(tiles: &mut [u8], shape: &[usize], axis: usize, start: usize, towards_start: bool) -> (LineSlide, Vec<TileMove>) {
+        let mut hash = zobrist::hash(tiles);
+        let mut trace = Vec::new();
+        let slide = slide_line(tiles, &mut hash, Lines::along(shape, axis), start, towards_start, &PowersOfTwo, Some(&mut trace));
+        assert_eq!(hash, zobrist::hash(tiles));
+        (slide, trace)
+    }
 
-pub fn transpose<const N: usize>(data: &mut [i32]) {
-    for i in 0..N {
-        for j in i..N {
-            data.swap(i + N * j, j + N * i);
-        }
+    #[test]
+    fn merged_tiles_point_at_both_tiles() {
+        let mut tiles = [1, 1, 0, 2];
+        let (slide, trace) = slide(&mut tiles, &[4], 0, 0, true);
+        assert_eq!(tiles, [2, 2, 0, 0]);
+        // The new 4 takes the place of the first 2, the second one merging into it
+        assert_eq!(
+            trace,
+            [
+                TileMove { from: 0, to: 0, merged_with: Some(1), tile: 2 },
+                TileMove { from: 3, to: 1, merged_with: None, tile: 2 },
+            ]
+        );
+        assert!(trace.iter().all(TileMove::is_moving));
+        assert_eq!((slide.score, slide.max_merged), (4, 2));
     }
-}
 
-pub fn transpose_3d<const N: usize>(data: &mut [i32]) {
-    for i in 0..N {
-        for j in i..N {
-            for k in 0..N {
-                // Swap elements for transposing along the first two dimensions (i, j)
-                let index1 = i + N * (j + N * k);
-                let index2 = j + N * (i + N * k);
-                data.swap(index1, index2);
-            }
-        }
+    #[test]
+    fn sliding_tiles_keep_their_value() {
+        // The second column of a 4x4 board, slid down
+        let mut tiles = [0; 16];
+        tiles[5] = 3;
+        tiles[13] = 1;
+        let (slide, trace) = slide(&mut tiles, &[4, 4], 0, 1, false);
+        assert_eq!((tiles[9], tiles[13], tiles[5]), (3, 1, 0));
+        assert_eq!(
+            trace,
+            [
+                TileMove { from: 13, to: 13, merged_with: None, tile: 1 },
+                TileMove { from: 5, to: 9, merged_with: None, tile: 3 },
+            ]
+        );
+        assert!(!trace[0].is_moving() && trace[1].is_moving());
+        assert_eq!((slide.score, slide.is_moving), (0, true));
     }
 }
@@ -0,0 +1,249 @@
+use crate::engine::AxisMove;
+use std::sync::OnceLock;
+
+/// A 4x4 board packed in a `u64`: 4 bits per cell holding its exponent, the first cell in the lowest bits
+/// and every row in 16 bits. Tiles up to 32768 fit, the larger ones and the typed tiles don't.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Bitboard(pub u64);
+
+/// A move of a `Bitboard`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PackedSlide {
+    pub board: Bitboard,
+    // Whether the board changed
+    pub moved: bool,
+    // The exponents of the tiles made by merges, 0 past the last one
+    pub merged: [u8; 8],
+}
+
+// One packed row slid towards its first cell
+#[derive(Clone, Copy, Default)]
+struct RowSlide {
+    row: u16,
+    // The exponents of the tiles made by merges, 0 for none
+    merged: [u8; 2],
+    // A merge made a tile above 15, which doesn't fit in the row
+    overflow: bool,
+}
+
+// The slide of every possible row, towards its first cell and towards its last one
+struct Tables {
+    left: Vec<RowSlide>,
+    right: Vec<RowSlide>,
+}
+
+fn tables() -> &'static Tables {
+    static TABLES: OnceLock<Tables> = OnceLock::new();
+    TABLES.get_or_init(|| Tables {
+        left: (0..=u16::MAX).map(slide_row_left).collect(),
+        right: (0..=u16::MAX).map(|row| reverse_slide(slide_row_left(reverse_row(row)))).collect(),
+    })
+}
+
+fn reverse_row(row: u16) -> u16 {
+    (row >> 12) | ((row >> 4) & 0x00F0) | ((row << 4) & 0x0F00) | (row << 12)
+}
+
+fn reverse_slide(slide: RowSlide) -> RowSlide {
+    RowSlide {
+        row: reverse_row(slide.row),
+        ..slide
+    }
+}
+
+// Equal tiles merge into the next one, once per slide, as in `algorithm::slide_left`
+fn slide_row_left(row: u16) -> RowSlide {
+    let tiles = (0..4).map(|i| (row >> (4 * i)) & 0xF).filter(|&tile| tile != 0);
+    let mut slide = RowSlide::default();
+    let mut cells = [0u16; 4];
+    let mut len = 0;
+    let mut merges = 0;
+    // The last tile placed, as long as it can still merge
+    let mut open = false;
+    for tile in tiles {
+        if open && cells[len - 1] == tile {
+            let merged = tile + 1;
+            slide.overflow |= merged > 15;
+            cells[len - 1] = merged & 0xF;
+            slide.merged[merges] = merged as u8;
+            merges += 1;
+            open = false;
+        } else {
+            cells[len] = tile;
+            len += 1;
+            open = true;
+        }
+    }
+    slide.row = cells.iter().enumerate().map(|(i, &tile)| tile << (4 * i)).fold(0, |row, cell| row | cell);
+    slide
+}
+
+impl Bitboard {
+    /// Packs the tiles of a 4x4 board, None when one of them doesn't fit in 4 bits.
+    pub fn from_tiles(tiles: &[u8]) -> Option<Self> {
+        let mut board = 0;
+        for (i, &tile) in tiles.iter().enumerate() {
+            if tile > 15 {
+                return None;
+            }
+            board |= (tile as u64) << (4 * i);
+        }
+        Some(Bitboard(board))
+    }
+
+    /// The exponent in `cell`.
+    pub fn tile(self, cell: usize) -> u8 {
+        ((self.0 >> (4 * cell)) & 0xF) as u8
+    }
+
+    /// The board mirrored along its diagonal, its rows becoming its columns.
+    pub fn transpose(self) -> Self {
+        let x = self.0;
+        let a1 = x & 0xF0F0_0F0F_F0F0_0F0F;
+        let a2 = x & 0x0000_F0F0_0000_F0F0;
+        let a3 = x & 0x0F0F_0000_0F0F_0000;
+        let a = a1 | (a2 << 12) | (a3 >> 12);
+        let b1 = a & 0xFF00_FF00_00FF_00FF;
+        let b2 = a & 0x00FF_00FF_0000_0000;
+        let b3 = a & 0x0000_0000_FF00_FF00;
+        Bitboard(b1 | (b2 >> 24) | (b3 << 24))
+    }
+
+    /// Whether `slide` changes the board, from the row tables alone.
+    pub fn can_slide(self, direction: AxisMove) -> bool {
+        let tables = tables();
+        let table = if direction.towards_start { &tables.left } else { &tables.right };
+        let board = if direction.axis == 0 { self.transpose() } else { self };
+        (0..4).any(|i| {
+            let row = ((board.0 >> (16 * i)) & 0xFFFF) as u16;
+            // An overflowing merge still moves the tiles
+            table[row as usize].row != row || table[row as usize].overflow
+        })
+    }
+
+    /// Slides the lines along the axis of the move, axis 0 being the columns and axis 1 the rows as in `Plane`.
+    /// The rows go through the row tables, the columns are transposed into rows and back.
+    /// None when a merge makes a tile that doesn't fit.
+    pub fn slide(self, direction: AxisMove) -> Option<PackedSlide> {
+        let tables = tables();
+        let table = if direction.towards_start { &tables.left } else { &tables.right };
+        let columns = direction.axis == 0;
+        let board = if columns { self.transpose() } else { self };
+
+        let mut slid = 0;
+        let mut merged = [0; 8];
+        let mut merges = 0;
+        for i in 0..4 {
+            let slide = table[((board.0 >> (16 * i)) & 0xFFFF) as usize];
+            if slide.overflow {
+                return None;
+            }
+            slid |= (slide.row as u64) << (16 * i);
+            for &tile in slide.merged.iter().filter(|&&tile| tile != 0) {
+                merged[merges] = tile;
+                merges += 1;
+            }
+        }
+        let slid = Bitboard(slid);
+        let slid = if columns { slid.transpose() } else { slid };
+        Some(PackedSlide {
+            board: slid,
+            moved: slid != self,
+            merged,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm;
+    use crate::engine::{Geometry, Lines};
+    use crate::game::{Direction, Game, Plane};
+    use crate::merge::PowersOfTwo;
+    use crate::state::GameState;
+    use rand::{Rng, SeedableRng};
+    use rand_xoshiro::Xoshiro256Plus;
+    use strum::IntoEnumIterator;
+
+    // A 4x4 game with the given tiles
+    fn game(tiles: &[u8; 16]) -> Game<4> {
+        let mut game = Game::<4>::with_seed(0);
+        for (cell, &tile) in tiles.iter().enumerate() {
+            game.new_tile(cell, tile as i32);
+        }
+        game
+    }
+
+    // Half the cells empty, the others up to 32768
+    fn random_tiles(rng: &mut Xoshiro256Plus) -> [u8; 16] {
+        std::array::from_fn(|_| if rng.gen_bool(0.5) { 0 } else { rng.gen_range(1..=15) })
+    }
+
+    #[test]
+    fn transpose_swaps_rows_and_columns() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(0);
+        for _ in 0..1000 {
+            let tiles = random_tiles(&mut rng);
+            let board = Bitboard::from_tiles(&tiles).unwrap();
+            let transposed = board.transpose();
+            for (cell, &tile) in tiles.iter().enumerate() {
+                assert_eq!(transposed.tile(cell % 4 * 4 + cell / 4), tile);
+            }
+            assert_eq!(transposed.transpose(), board);
+        }
+    }
+
+    // The packed moves of the search and the generic ones of the traced moves make the same games
+    #[test]
+    fn packed_moves_match_the_generic_ones() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(1);
+        for _ in 0..20_000 {
+            let tiles = random_tiles(&mut rng);
+            let game = game(&tiles);
+            let board = Bitboard::from_tiles(&tiles).unwrap();
+            let mut generic_legal = Vec::new();
+            for direction in Direction::iter() {
+                let (mut packed, mut generic) = (game.clone(), game.clone());
+                let moved = packed.movement(&direction);
+                let generic_moved = !generic.traced_movement(&direction).is_empty();
+                assert_eq!(moved, generic_moved, "{:?} {:?}", tiles, direction);
+                assert_eq!(packed.data(), generic.data(), "{:?} {:?}", tiles, direction);
+                assert_eq!(packed.score(), generic.score());
+                assert_eq!(packed.zobrist(), generic.zobrist());
+
+                let axis_move = Plane::<4, 4>::axis_move(direction);
+                let lines = Lines::along(&[4, 4], axis_move.axis);
+                let can_slide = algorithm::can_slide_along(&tiles, lines.length, lines.stride, axis_move.towards_start, &PowersOfTwo);
+                assert_eq!(board.can_slide(axis_move), can_slide, "{:?} {:?}", tiles, direction);
+                if generic_moved {
+                    generic_legal.push(direction);
+                }
+            }
+            assert_eq!(game.legal_moves().iter().collect::<Vec<_>>(), generic_legal);
+        }
+    }
+
+    // Two 32768s merge into a tile that doesn't fit in 4 bits, the engine slides that line the generic way
+    #[test]
+    fn overflowing_merges_fall_back() {
+        let mut tiles = [0; 16];
+        tiles[0] = 15;
+        tiles[1] = 15;
+        let board = Bitboard::from_tiles(&tiles).unwrap();
+        let left = Plane::<4, 4>::axis_move(Direction::Left);
+        assert!(board.slide(left).is_none());
+        assert!(board.can_slide(left));
+
+        let (mut packed, mut generic) = (game(&tiles), game(&tiles));
+        assert!(packed.legal_moves().contains(Direction::Left));
+        assert!(packed.movement(&Direction::Left));
+        assert_eq!(packed.data()[0], 16);
+        assert_eq!(packed.score(), 65536);
+        generic.traced_movement(&Direction::Left);
+        assert_eq!(packed.data(), generic.data());
+        assert_eq!(packed.zobrist(), generic.zobrist());
+        // Once a tile is past 15 the board doesn't pack at all
+        assert!(Bitboard::from_tiles(packed.data()).is_none());
+    }
+}
@@ -1,15 +1,41 @@
-use std::{borrow::Cow, usize};
+use std::borrow::Cow;
 
+use crate::bonus::{self, TileType};
+use crate::merge::MergeRule;
+use crossterm::style::Stylize;
 use matrix_display::*;
 use rand::{Rng, SeedableRng};
 use rand_xoshiro::Xoshiro256Plus;
 
+const FORMAT: Format = Format {
+    cell_w: 7,
+    cell_h: 3,
+};
+
+// Space between the boards drawn side by side
+const GAP: &str = "  ";
+
+/// One of the boards drawn by `Board::print_side_by_side`.
+pub struct Panel<'a> {
+    pub board: Board,
+    pub title: String,
+    // Whether the title stands out, e.g. the layer the last move slid the tiles towards
+    pub highlighted: bool,
+    pub data: &'a [u8],
+}
+
 #[derive(Clone)]
 pub struct Board {
     colour_theme: [i32; 19],
     rng: Xoshiro256Plus,
 }
 
+impl Default for Board {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Board {
     pub fn new() -> Board {
         let colour_theme = [
@@ -21,69 +47,122 @@ impl Board {
             rng: Xoshiro256Plus::from_entropy(),
         }
     }
-    pub fn print<W, const N : usize>(&self, data: &Vec<i32>, out: &mut W)
-    where
-        W: ::std::io::Write,
-    {
-        let mut matrix = matrix::Matrix::new(
-            N,
-            data.into_iter()
+    // The tiles as a matrix of coloured cells, `columns` wide
+    fn matrix(&self, data: &[u8], columns: usize, merge: &dyn MergeRule) -> matrix::Matrix<cell::Cell<Cow<'static, str>>> {
+        matrix::Matrix::new(
+            columns,
+            data.iter()
+                .map(|&i| i as i32)
                 .map(|i| {
                     (
-                        2i32.pow(*i as u32),
-                        *self.colour_theme.get(*i as usize).unwrap() as u8,
+                        i,
+                        // Tiles above the theme all get its last colour, the typed ones that of their exponent
+                        *self.colour_theme.get(bonus::exponent(i) as usize).unwrap_or(&self.colour_theme[18]) as u8,
                     )
                 })
-                .map(|(x, col)| {
+                .map(|(tile, col)| {
                     (
-                        if x == 1 {
-                            Cow::Borrowed(".")
-                        } else {
-                            Cow::Owned(x.to_string())
+                        match (tile, TileType::of(tile)) {
+                            (0, _) => Cow::Borrowed("."),
+                            (_, TileType::Number) => Cow::Owned(merge.value(tile).to_string()),
+                            // Bonus tiles stand out, the bonused ones are about to go
+                            (_, TileType::Bonus) => Cow::Owned(format!("+{}", merge.value(tile))),
+                            (_, TileType::Bonused) => Cow::Owned(format!("({})", merge.value(tile))),
                         },
                         col,
                     )
                 })
                 .map(|(s, col)| cell::Cell::new(s, 0, col))
                 .collect::<Vec<_>>(),
-        );
-        const FORMAT: Format = Format {
-            cell_w: 7,
-            cell_h: 3,
-        };
+        )
+    }
+    pub fn print<W>(&self, data: &[u8], columns: usize, merge: &dyn MergeRule, out: &mut W)
+    where
+        W: ::std::io::Write,
+    {
+        let mut matrix = self.matrix(data, columns, merge);
         let display = MatrixDisplay::new(&FORMAT, &mut matrix);
         display.print(out, &style::BordersStyle::Heavy);
     }
-    pub fn print_inactive<W, const N : usize>(&self, data: &Vec<i32>, out: &mut W)
+    // The printed lines of the board without their line breaks, each with its own colour codes
+    // so that other text can be written next to it
+    fn lines(&self, data: &[u8], columns: usize, merge: &dyn MergeRule) -> Vec<String> {
+        let mut matrix = self.matrix(data, columns, merge);
+        let display = MatrixDisplay::new(&FORMAT, &mut matrix);
+        let mut lines = vec![String::new()];
+        for segment in display.render(&style::BordersStyle::Heavy) {
+            let ends_line = segment.ends_with("\r\n");
+            lines.last_mut().unwrap().push_str(&segment.to_string().replace("\r\n", ""));
+            if ends_line {
+                lines.push(String::new());
+            }
+        }
+        lines.pop();
+        lines
+    }
+    /// Draws boards next to each other, each under its title, e.g. the layers of a 3D board.
+    pub fn print_side_by_side<W>(panels: &[Panel], columns: usize, merge: &dyn MergeRule, out: &mut W) -> std::io::Result<()>
     where
         W: ::std::io::Write,
     {
+        let width = columns * (FORMAT.cell_w + 1) + 1;
+        for (i, panel) in panels.iter().enumerate() {
+            let title = format!("{:^width$}", panel.title, width = width);
+            let title = if panel.highlighted { title.reverse() } else { title.stylize() };
+            write!(out, "{}{}", if i > 0 { GAP } else { "" }, title)?;
+        }
+        write!(out, "\r\n")?;
+        let boards: Vec<Vec<String>> = panels
+            .iter()
+            .map(|panel| panel.board.lines(panel.data, columns, merge))
+            .collect();
+        for row in 0..boards[0].len() {
+            let line: Vec<&str> = boards.iter().map(|lines| lines[row].as_str()).collect();
+            write!(out, "{}\r\n", line.join(GAP))?;
+        }
+        Ok(())
+    }
+    /// The theme of `print_inactive`.
+    pub fn grey_scale(&self) -> Board {
         let mut grey_scale = self.clone();
         grey_scale.colour_theme = [
             0, 255, 251, 248, 246, 244, 242, 241, 240, 239, 238, 237, 236, 235, 234, 233, 232, 231, 230
         ];
-        grey_scale.print::<W, N>(data, out);
+        grey_scale
     }
-    pub fn print_lost<W, const N : usize>(&self, data: &Vec<i32>, out: &mut W)
-    where
-        W: ::std::io::Write,
-    {
+    /// The theme of `print_lost`.
+    pub fn red_scale(&self) -> Board {
         let mut red_scale = self.clone();
         red_scale.colour_theme = [
             0, 90, 126, 162, 198, 197, 161, 125, 89, 53, 17, 196, 160, 124, 88, 52, 16, 12, 10
         ];
-        red_scale.print::<W, N>(data, out);
+        red_scale
     }
-    pub fn print_won<W, const N : usize>(&self, data: &Vec<i32>, out: &mut W)
+    /// The theme of `print_won`.
+    pub fn fireworks(&self) -> Board {
+        let mut fireworks = self.clone();
+        // A random colour for every tile of the theme, the empty cells keeping theirs
+        for colour in fireworks.colour_theme[1..].iter_mut() {
+            *colour = fireworks.rng.gen_range(1..256);
+        }
+        fireworks
+    }
+    pub fn print_inactive<W>(&self, data: &[u8], columns: usize, merge: &dyn MergeRule, out: &mut W)
     where
         W: ::std::io::Write,
     {
-        let mut fireworks = self.clone();
-        let mut fw: [i32; 17] = [0; 17];
-        fw.iter_mut()
-            .for_each(|f| *f = fireworks.rng.gen_range(1..256));
-        fw[0] = 0;
-        fireworks.colour_theme[..].clone_from_slice(&fw[..]);
-        fireworks.print::<W, N>(data, out);
+        self.grey_scale().print(data, columns, merge, out);
+    }
+    pub fn print_lost<W>(&self, data: &[u8], columns: usize, merge: &dyn MergeRule, out: &mut W)
+    where
+        W: ::std::io::Write,
+    {
+        self.red_scale().print(data, columns, merge, out);
+    }
+    pub fn print_won<W>(&self, data: &[u8], columns: usize, merge: &dyn MergeRule, out: &mut W)
+    where
+        W: ::std::io::Write,
+    {
+        self.fireworks().print(data, columns, merge, out);
     }
 }
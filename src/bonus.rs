@@ -0,0 +1,197 @@
+use crate::merge::MergeRule;
+use rand::{Rng, RngCore};
+use std::sync::Arc;
+
+// Typed tiles are stored with their exponent offset by these, see `TileType`
+const BONUS: i32 = 64;
+const BONUSED: i32 = 128;
+
+/// The types of the tiles of the 2048-3d web version (`type` in `game_manager.js`).
+/// Only tiles of the same type merge, and the type is part of the stored tile:
+/// a bonus tile is its exponent plus 64, a bonused one its exponent plus 128.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TileType {
+    // The tiles of the classic game
+    Number,
+    // Spawned by `Bonus`, on top of the regular spawns
+    Bonus,
+    // Made by merging two bonus tiles, removed when the next move is played
+    Bonused,
+}
+
+impl TileType {
+    pub fn of(tile: i32) -> Self {
+        match tile {
+            BONUSED.. => TileType::Bonused,
+            BONUS.. => TileType::Bonus,
+            _ => TileType::Number,
+        }
+    }
+
+    /// The tile of this type with the given exponent.
+    pub fn tile(self, exponent: i32) -> i32 {
+        match self {
+            TileType::Number => exponent,
+            TileType::Bonus => exponent + BONUS,
+            TileType::Bonused => exponent + BONUSED,
+        }
+    }
+}
+
+/// The exponent of a tile, whatever its type.
+pub fn exponent(tile: i32) -> i32 {
+    tile % BONUS
+}
+
+/// Another merge rule between tiles of the same type, see `TileType`.
+/// Two bonus tiles merge into a bonused one, which merges no further, and the tiles are worth what the rule says of their exponent.
+#[derive(Clone, Debug)]
+pub struct Typed {
+    pub rule: Arc<dyn MergeRule>,
+}
+
+impl MergeRule for Typed {
+    fn merge(&self, a: i32, b: i32) -> Option<i32> {
+        let kind = TileType::of(a);
+        if kind != TileType::of(b) {
+            return None;
+        }
+        // Past 63 the exponent would read as another type
+        let merged = self.rule.merge(exponent(a), exponent(b)).filter(|&merged| merged < BONUS)?;
+        match kind {
+            TileType::Number => Some(merged),
+            TileType::Bonus => Some(TileType::Bonused.tile(merged)),
+            TileType::Bonused => None,
+        }
+    }
+
+    fn value(&self, tile: i32) -> u64 {
+        self.rule.value(exponent(tile))
+    }
+}
+
+/// `addBonus` of the 2048-3d web version: after a move, a bonus tile may appear on top of the regular spawns,
+/// more likely the emptier the board. Its value is drawn among `values`, and nothing appears when the board
+/// already has `per_value` bonus tiles of that value or `total` bonus tiles overall.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bonus {
+    // The exponents of the bonus tiles, all equally likely
+    pub values: Vec<i32>,
+    // Chance of a bonus on an empty board, scaled by the share of empty cells
+    pub rate: f64,
+    pub per_value: usize,
+    pub total: usize,
+}
+
+impl Bonus {
+    /// The bonus tiles of the web version: 4, 64 or 256, at most 2 of each and 4 overall.
+    pub fn web() -> Self {
+        Bonus {
+            values: vec![2, 6, 8],
+            rate: 0.2,
+            per_value: 2,
+            total: 4,
+        }
+    }
+
+    /// The bonus tiles that can appear on `data` after a move, with their probabilities.
+    /// They sum to less than 1, no bonus tile appearing the rest of the time.
+    pub fn outcomes(&self, data: &[u8]) -> Vec<(i32, f64)> {
+        let empty = data.iter().filter(|&&tile| tile == 0).count();
+        if empty == 0 {
+            return Vec::new();
+        }
+        // The bonus tiles leave the board when they merge, so the board tells how many are out
+        let bonus: Vec<i32> = data
+            .iter()
+            .map(|&tile| tile as i32)
+            .filter(|&tile| TileType::of(tile) == TileType::Bonus)
+            .map(exponent)
+            .collect();
+        if bonus.len() >= self.total {
+            return Vec::new();
+        }
+        let p = self.rate * empty as f64 / data.len() as f64 / self.values.len() as f64;
+        self.values
+            .iter()
+            .filter(|&&value| bonus.iter().filter(|&&tile| tile == value).count() < self.per_value)
+            .map(|&value| (TileType::Bonus.tile(value), p))
+            .collect()
+    }
+
+    /// Draws the bonus tile appearing on `data`, if any.
+    pub fn sample(&self, data: &[u8], rng: &mut dyn RngCore) -> Option<i32> {
+        let mut x: f64 = rng.gen();
+        for (tile, p) in self.outcomes(data) {
+            if x < p {
+                return Some(tile);
+            }
+            x -= p;
+        }
+        None
+    }
+}
+
+impl Default for Bonus {
+    fn default() -> Self {
+        Self::web()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merge::{MergeCap, PowersOfTwo};
+
+    fn typed() -> Typed {
+        Typed { rule: Arc::new(PowersOfTwo) }
+    }
+
+    #[test]
+    fn only_tiles_of_the_same_type_merge() {
+        let bonus = TileType::Bonus.tile(2);
+        assert_eq!(typed().merge(2, 2), Some(3));
+        assert_eq!(typed().merge(bonus, bonus), Some(TileType::Bonused.tile(3)));
+        assert_eq!(typed().merge(bonus, 2), None);
+        assert_eq!(typed().merge(2, bonus), None);
+        assert_eq!(typed().value(bonus), 4);
+    }
+
+    #[test]
+    fn bonused_tiles_merge_no_further() {
+        let bonused = TileType::Bonused.tile(3);
+        assert_eq!(TileType::of(bonused), TileType::Bonused);
+        assert_eq!(typed().merge(bonused, bonused), None);
+        assert_eq!(typed().merge(bonused, TileType::Bonus.tile(3)), None);
+        assert_eq!(typed().value(bonused), 8);
+        // Nor do the largest tiles, into one of another type
+        assert_eq!(typed().merge(63, 63), None);
+    }
+
+    #[test]
+    fn the_cap_of_the_rule_holds_for_every_type() {
+        let capped = Typed { rule: Arc::new(MergeCap::new(Arc::new(PowersOfTwo), 8)) };
+        assert_eq!(capped.merge(2, 2), Some(3));
+        assert_eq!(capped.merge(3, 3), None);
+        assert_eq!(capped.merge(TileType::Bonus.tile(2), TileType::Bonus.tile(2)), Some(TileType::Bonused.tile(3)));
+        assert_eq!(capped.merge(TileType::Bonus.tile(3), TileType::Bonus.tile(3)), None);
+    }
+
+    #[test]
+    fn bonus_tiles_stop_at_their_caps() {
+        let bonus = Bonus::web();
+        let mut data = [0u8; 16];
+        assert_eq!(bonus.outcomes(&data).len(), 3);
+        // Two bonus 4s already out, no third one
+        data[0] = TileType::Bonus.tile(2) as u8;
+        data[1] = TileType::Bonus.tile(2) as u8;
+        let tiles: Vec<i32> = bonus.outcomes(&data).iter().map(|&(tile, _)| tile).collect();
+        assert_eq!(tiles, [TileType::Bonus.tile(6), TileType::Bonus.tile(8)]);
+        // Four bonus tiles out, none at all
+        data[2] = TileType::Bonus.tile(6) as u8;
+        data[3] = TileType::Bonus.tile(8) as u8;
+        assert!(bonus.outcomes(&data).is_empty());
+        // Nor on a full board
+        assert!(bonus.outcomes(&[1; 16]).is_empty());
+    }
+}
@@ -0,0 +1,367 @@
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use game_2048::bonus::Bonus;
+use game_2048::merge::{MergeCap, MergeRuleName};
+use game_2048::notation::Notation;
+use game_2048::rules::Rules;
+use std::sync::Arc;
+use game_2048::spawn::SpawnSpec;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+#[derive(Parser, Debug)]
+#[command(name = "game-2048", about = "2048 in the terminal, with an Expectimax AI")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Play a game yourself
+    Play(PlayArgs),
+    /// Watch the AI play and log the results
+    Ai(AiArgs),
+    /// Run the AI headless and report timings and results
+    Bench(AiArgs),
+    /// Summarize a results CSV written by `ai` or `bench`
+    Analyze(AnalyzeArgs),
+    /// Convert a results CSV from before scores were tile values, whose
+    /// Score column is the sum of the merged exponents
+    Migrate(MigrateArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct PlayArgs {
+    /// Size of the board: its side length, or rows x columns such as 3x5 (2 to 8 each)
+    #[arg(short = 'n', long, default_value = "4")]
+    pub size: BoardSize,
+    /// Number of dimensions of the board: 2, or 3 for a cube
+    #[arg(long, default_value_t = 2, value_parser = clap::value_parser!(u8).range(2..=3))]
+    pub dimensions: u8,
+    /// Seed of the game, for the same tiles as a previous game
+    #[arg(long)]
+    pub seed: Option<u64>,
+    /// Start from this position instead of a new board, e.g. "4x4:0,1,0,0/0,0,0,0/0,0,2,0/0,0,0,0 player 4"
+    /// (tiles as exponents, see the README). Its shape sets the board size and dimensions.
+    #[arg(long, value_parser = parse_position)]
+    pub position: Option<Notation>,
+    /// Number of moves that can be undone (u) and redone (Ctrl-R)
+    #[arg(long, default_value_t = 1000)]
+    pub undo_limit: usize,
+    #[command(flatten)]
+    pub rules: RuleArgs,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct AiArgs {
+    /// Size of the board: its side length, or rows x columns such as 3x5 (2 to 8 each)
+    #[arg(short = 'n', long, default_value = "4")]
+    pub size: BoardSize,
+    /// Number of dimensions of the board: 2, 3 for a cube or 4 for a hypercube
+    #[arg(long, default_value_t = 2, value_parser = clap::value_parser!(u8).range(2..=4))]
+    pub dimensions: u8,
+    /// How many moves ahead the strategy searches
+    #[arg(short, long, default_value_t = 2)]
+    pub depth: usize,
+    /// Only try this many of the empty cells, drawn at random, where the search spawns a tile.
+    /// Keeps the large boards, such as a 4x4x4 cube, fast enough to search 2 or 3 moves ahead
+    #[arg(long)]
+    pub samples: Option<usize>,
+    /// Evaluate the positions less likely than this to be reached instead of searching further, e.g. 0.0001
+    #[arg(long, default_value_t = 0.0)]
+    pub min_probability: f64,
+    /// Memory of the search cache in MiB. Once it is full, the positions of the earlier moves
+    /// and those searched least deep give way to the new ones
+    #[arg(long, default_value_t = 64)]
+    pub cache_mb: usize,
+    /// Number of games to play
+    #[arg(short, long, default_value_t = 1)]
+    pub games: usize,
+    /// Seed of the first game, the following games use seed + 1, seed + 2, ...
+    /// Every game's seed is in the Seed column of the CSV, to replay it.
+    #[arg(long)]
+    pub seed: Option<u64>,
+    /// Start from this position instead of a new board, e.g. "4x4:0,1,0,0/0,0,0,0/0,0,2,0/0,0,0,0 player 4"
+    /// (tiles as exponents, see the README). Its shape sets the board size and dimensions.
+    #[arg(long, value_parser = parse_position)]
+    pub position: Option<Notation>,
+    #[command(flatten)]
+    pub rules: RuleArgs,
+    /// Strategy used to pick the moves
+    #[arg(short, long, value_enum, default_value_t = StrategyName::Expectimax)]
+    pub strategy: StrategyName,
+    #[command(flatten)]
+    pub weights: Weights,
+    /// Where to write one CSV row per game
+    #[arg(long, default_value = "results.csv")]
+    pub csv: PathBuf,
+    /// Where to write the final board of every game
+    #[arg(long, default_value = "boards.txt")]
+    pub boards: PathBuf,
+}
+
+impl PlayArgs {
+    pub fn board_size(&self) -> BoardSize {
+        board_size(self.size, self.position.as_ref())
+    }
+
+    pub fn dimensions(&self) -> u8 {
+        dimensions(self.dimensions, self.position.as_ref())
+    }
+}
+
+impl AiArgs {
+    pub fn board_size(&self) -> BoardSize {
+        board_size(self.size, self.position.as_ref())
+    }
+
+    pub fn dimensions(&self) -> u8 {
+        dimensions(self.dimensions, self.position.as_ref())
+    }
+}
+
+// The dimensions of the starting position when there is one
+fn dimensions(dimensions: u8, position: Option<&Notation>) -> u8 {
+    position.map_or(dimensions, |position| position.shape.len() as u8)
+}
+
+// The size of the starting position when there is one
+fn board_size(size: BoardSize, position: Option<&Notation>) -> BoardSize {
+    position.map_or(size, |position| {
+        let shape = &position.shape;
+        BoardSize {
+            rows: shape[shape.len() - 2] as u8,
+            columns: shape[shape.len() - 1] as u8,
+        }
+    })
+}
+
+fn parse_position(s: &str) -> Result<Notation, String> {
+    let position: Notation = s.parse()?;
+    if let Some(length) = position.shape.iter().find(|length| !(2..=8).contains(*length)) {
+        return Err(format!("board size {} is not between 2 and 8", length));
+    }
+    match position.shape.len() {
+        // 3D and 4D boards are hypercubes
+        3 | 4 => position.expect_shape(&vec![position.shape[0]; position.shape.len()])?,
+        2 => {}
+        _ => return Err("positions are 2D, 3D or 4D boards".to_string()),
+    }
+    Ok(position)
+}
+
+/// The size of a board, `4` for a square board or `3x5` for 3 rows of 5 columns.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BoardSize {
+    pub rows: u8,
+    pub columns: u8,
+}
+
+impl BoardSize {
+    pub fn square(side: u8) -> Self {
+        BoardSize { rows: side, columns: side }
+    }
+
+    pub fn is_square(&self) -> bool {
+        self.rows == self.columns
+    }
+}
+
+impl FromStr for BoardSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let length = |length: &str| match length.trim().parse::<u8>() {
+            Ok(length) if (2..=8).contains(&length) => Ok(length),
+            _ => Err(format!("invalid board length '{}', expected 2 to 8", length)),
+        };
+        match s.split_once('x') {
+            Some((rows, columns)) => Ok(BoardSize { rows: length(rows)?, columns: length(columns)? }),
+            None => length(s).map(BoardSize::square),
+        }
+    }
+}
+
+impl Default for AiArgs {
+    fn default() -> Self {
+        AiArgs {
+            size: BoardSize::square(4),
+            dimensions: 2,
+            depth: 2,
+            samples: None,
+            min_probability: 0.0,
+            cache_mb: 64,
+            games: 1,
+            seed: None,
+            position: None,
+            rules: RuleArgs::default(),
+            strategy: StrategyName::Expectimax,
+            weights: Weights::default(),
+            csv: PathBuf::from("results.csv"),
+            boards: PathBuf::from("boards.txt"),
+        }
+    }
+}
+
+/// The variant of the game to play.
+#[derive(Args, Debug, Clone)]
+pub struct RuleArgs {
+    /// Values of the spawned tiles: classic (90% 2, 10% 4), even, fours, eighty-twenty, eights,
+    /// or tile:weight pairs such as 2:90,4:10. The presets spawn the first tiles of the merge rule,
+    /// the pairs the tiles they name, which must be tiles of the merge rule (3:90,9:10 with threes)
+    #[arg(long, default_value = "classic")]
+    pub spawn: SpawnSpec,
+    /// Number of tiles spawned after every move
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u8).range(1..))]
+    pub spawn_tiles: u8,
+    /// Which tiles merge: classic (equal powers of two), threes (equal powers of three)
+    /// or fibonacci (1, 2, 3, 5, 8, ... where consecutive numbers merge)
+    #[arg(long, default_value = "classic")]
+    pub merge: MergeRuleName,
+    /// Largest tile a merge can make
+    #[arg(long)]
+    pub merge_cap: Option<u64>,
+    /// Tile that wins the game, you can keep playing after reaching it
+    #[arg(long, default_value_t = 2048)]
+    pub target: u64,
+    /// Add the bonus tiles of the 2048-3d web version: 4, 64 or 256 tiles that only merge with each other
+    /// and leave the board on the move after their merge
+    #[arg(long)]
+    pub bonus: bool,
+}
+
+impl RuleArgs {
+    /// The rules of the variant, an error when `--spawn` names tiles the merge rule doesn't have.
+    pub fn rules(&self) -> Result<Rules, std::io::Error> {
+        let mut merge = self.merge.rule();
+        if let Some(cap) = self.merge_cap {
+            merge = Arc::new(MergeCap::new(merge, cap));
+        }
+        let spawn = self
+            .spawn
+            .resolve(&*merge)
+            .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("--spawn: {}", error)))?;
+        let rules = Rules::new(spawn.with_tiles_per_turn(self.spawn_tiles as usize))
+            .with_merge(merge)
+            .with_target(self.target);
+        if self.bonus {
+            Ok(rules.with_bonus(Bonus::web()))
+        } else {
+            Ok(rules)
+        }
+    }
+}
+
+impl Default for RuleArgs {
+    fn default() -> Self {
+        RuleArgs {
+            spawn: SpawnSpec::default(),
+            spawn_tiles: 1,
+            merge: MergeRuleName::Classic,
+            merge_cap: None,
+            bonus: false,
+            target: 2048,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StrategyName {
+    /// Expectimax over the snake shape and empty tiles
+    Expectimax,
+    /// Expectimax over the weighted heuristics (--alpha ... --lambda)
+    ExpectimaxWeighted,
+}
+
+/// Weights of the heuristics used by `expectimax-weighted`. By default the snake shape,
+/// pulled towards the first corner.
+#[derive(Args, Debug, Clone)]
+pub struct Weights {
+    /// Weight of the max tile
+    #[arg(long, default_value_t = 0.0, allow_hyphen_values = true)]
+    pub alpha: f64,
+    /// Weight of the (inverse squared) number of occupied tiles
+    #[arg(long, default_value_t = 0.0, allow_hyphen_values = true)]
+    pub beta: f64,
+    /// Weight of keeping tiles out of the center
+    #[arg(long, default_value_t = 0.0, allow_hyphen_values = true)]
+    pub gamma: f64,
+    /// Weight of the snake shape sum
+    #[arg(long, default_value_t = 1.0, allow_hyphen_values = true)]
+    pub delta: f64,
+    /// Weight of the tiles' distance from the first corner, negative to keep them in it
+    #[arg(long, default_value_t = -1000.0, allow_hyphen_values = true)]
+    pub lambda: f64,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Weights {
+            alpha: 0.0,
+            beta: 0.0,
+            gamma: 0.0,
+            delta: 1.0,
+            lambda: -1000.0,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct AnalyzeArgs {
+    /// Results CSV to summarize
+    #[arg(default_value = "results.csv")]
+    pub csv: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct MigrateArgs {
+    /// Results CSV to convert
+    pub csv: PathBuf,
+    /// Where to write the converted file, defaults to overwriting the input
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+/// Calls the const generic function `$f::<R, C>` with the board shape known only at runtime.
+macro_rules! with_board_shape {
+    ($size:expr, $f:ident ( $($arg:expr),* $(,)? )) => {
+        match $size.rows {
+            2 => with_board_shape!(@columns 2, $size.columns, $f($($arg),*)),
+            3 => with_board_shape!(@columns 3, $size.columns, $f($($arg),*)),
+            4 => with_board_shape!(@columns 4, $size.columns, $f($($arg),*)),
+            5 => with_board_shape!(@columns 5, $size.columns, $f($($arg),*)),
+            6 => with_board_shape!(@columns 6, $size.columns, $f($($arg),*)),
+            7 => with_board_shape!(@columns 7, $size.columns, $f($($arg),*)),
+            8 => with_board_shape!(@columns 8, $size.columns, $f($($arg),*)),
+            rows => unreachable!("{} rows are rejected by the argument parser", rows),
+        }
+    };
+    (@columns $rows:literal, $columns:expr, $f:ident ( $($arg:expr),* )) => {
+        match $columns {
+            2 => $f::<$rows, 2>($($arg),*),
+            3 => $f::<$rows, 3>($($arg),*),
+            4 => $f::<$rows, 4>($($arg),*),
+            5 => $f::<$rows, 5>($($arg),*),
+            6 => $f::<$rows, 6>($($arg),*),
+            7 => $f::<$rows, 7>($($arg),*),
+            8 => $f::<$rows, 8>($($arg),*),
+            columns => unreachable!("{} columns are rejected by the argument parser", columns),
+        }
+    };
+}
+
+/// Calls the const generic function `$f::<N>` with the board size known only at runtime.
+macro_rules! with_board_size {
+    ($size:expr, $f:ident ( $($arg:expr),* $(,)? )) => {
+        match $size {
+            2 => $f::<2>($($arg),*),
+            3 => $f::<3>($($arg),*),
+            4 => $f::<4>($($arg),*),
+            5 => $f::<5>($($arg),*),
+            6 => $f::<6>($($arg),*),
+            7 => $f::<7>($($arg),*),
+            8 => $f::<8>($($arg),*),
+            size => unreachable!("board size {} is rejected by the argument parser", size),
+        }
+    };
+}
@@ -1,11 +1,14 @@
-use super::board;
+use super::board::{self, Panel};
+use super::engine::Geometry;
 use super::game;
+use super::three_d_game::{Cube, ThreeDDirection, ThreeDGame};
 use crossterm::terminal::ClearType;
 use crossterm::QueueableCommand;
 use game::GameStatus;
+use super::state::GameState;
 use std::io::Write;
 
-fn header<W>(out: &mut W, score: i32) -> Result<(), std::io::Error>
+fn header<W>(out: &mut W, score: u64) -> Result<(), std::io::Error>
 where
     W: Write,
 {
@@ -17,7 +20,11 @@ where
     )
 }
 
-fn footer<W>(out: &mut W, status: &GameStatus) -> Result<(), std::io::Error>
+// The keys of a 2D game, and of a cube (see `human::play_3d`)
+const KEYS: &str = "    [ ← ↑ → ↓ ], q for quit\r\n";
+const CUBE_KEYS: &str = "    [ ← ↑ → ↓ q e ], 1-9 hide/show, v rotate, esc for quit\r\n";
+
+fn footer<W>(out: &mut W, status: &GameStatus, keys: &str) -> Result<(), std::io::Error>
 where
     W: Write,
 {
@@ -25,10 +32,10 @@ where
         out,
         "{}",
         match status {
-            GameStatus::Ongoing => "    [ ← ↑ → ↓ ], q for quit\r\n",
-            GameStatus::Lost => "    [  🎮 ⛔  ], q for quit\r\n",
+            GameStatus::Ongoing => keys,
+            GameStatus::Lost => "    [  🎮 ⛔  ], r to restart, u to undo, q for quit\r\n",
             GameStatus::Interrupted => "    [  🎮 🚦  ], quit? (y/n)\r\n",
-            GameStatus::Won => "    [ 🎉🎉🎉 ], quit? (y/n)\r\n",
+            GameStatus::Won => "    [ 🎉🎉🎉 ], quit? (y/n) n keeps playing\r\n",
         }
     )
 }
@@ -42,22 +49,179 @@ where
         .queue(crossterm::cursor::MoveTo(1, 1))
 }
 
-pub fn display_game<'a, W, const N: usize>(
+/// Draws the game, a 3D board with its layers one below the other.
+pub fn display_game<'a, W, G>(
     out: &'a mut W,
     board: &board::Board,
-    game: &game::Game<N>,
+    game: &G,
 ) -> Result<&'a mut W, std::io::Error>
 where
     W: Write,
+    G: GameState,
 {
     header(clear(out)?, game.score())?;
     let status = game.status();
+    let merge = &*game.rules().merge;
+    let columns = G::SHAPE[G::SHAPE.len() - 1];
     match status {
-        GameStatus::Ongoing => board.print::<W, N>(game.data(), out),
-        GameStatus::Lost => board.print_lost::<W, N>(game.data(), out),
-        GameStatus::Interrupted => board.print_inactive::<W, N>(game.data(), out),
-        GameStatus::Won => board.print_won::<W, N>(game.data(), out),
+        GameStatus::Ongoing => board.print(game.data(), columns, merge, out),
+        GameStatus::Lost => board.print_lost(game.data(), columns, merge, out),
+        GameStatus::Interrupted => board.print_inactive(game.data(), columns, merge, out),
+        GameStatus::Won => board.print_won(game.data(), columns, merge, out),
+    };
+    footer(out, status, KEYS)?;
+    Ok(out)
+}
+
+/// How `display_cube` shows the layers of a cube, the views of the 2048-3d web version.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CubeView {
+    // Seen from the side: the slices along the columns side by side instead of the layers,
+    // each with the layers from left to right
+    pub rotated: bool,
+    // The slices left out of the drawing, one bit per slice
+    pub hidden: u16,
+}
+
+impl CubeView {
+    pub fn rotate(&mut self) {
+        self.rotated = !self.rotated;
+    }
+
+    /// Hides `slice`, or shows it again if it was hidden.
+    pub fn toggle(&mut self, slice: usize) {
+        self.hidden ^= 1 << slice;
+    }
+
+    pub fn is_hidden(&self, slice: usize) -> bool {
+        self.hidden >> slice & 1 == 1
+    }
+}
+
+/// Draws a 3D game with its `N` layers side by side, or the slices along its columns when rotated.
+/// The last move is marked: an arrow on every slice when it slid the tiles within them,
+/// otherwise the slice it slid the tiles towards stands out.
+pub fn display_cube<'a, W, const N: usize>(
+    out: &'a mut W,
+    board: &board::Board,
+    game: &ThreeDGame<N>,
+    view: &CubeView,
+    last_move: Option<ThreeDDirection>,
+) -> Result<&'a mut W, std::io::Error>
+where
+    W: Write,
+{
+    header(clear(out)?, game.score())?;
+    let status = game.status();
+    let theme = match status {
+        GameStatus::Ongoing => board.clone(),
+        GameStatus::Lost => board.red_scale(),
+        GameStatus::Interrupted => board.grey_scale(),
+        GameStatus::Won => board.fireworks(),
     };
-    footer(out, status)?;
+    // The axes of the board drawn as the slices and as the columns of each slice, the rows are always rows
+    let (slice_axis, column_axis, name) = if view.rotated { (2, 0, "Column") } else { (0, 2, "Layer") };
+    let last_move = last_move.map(Cube::<N>::axis_move);
+    let slices: Vec<Vec<u8>> = (0..N)
+        .map(|slice| {
+            (0..N * N)
+                .map(|cell| {
+                    let mut coordinates = [0; 3];
+                    coordinates[slice_axis] = slice;
+                    coordinates[1] = cell / N;
+                    coordinates[column_axis] = cell % N;
+                    game.data()[(coordinates[0] * N + coordinates[1]) * N + coordinates[2]]
+                })
+                .collect()
+        })
+        .collect();
+    let panels: Vec<Panel> = slices
+        .iter()
+        .enumerate()
+        .filter(|&(slice, _)| !view.is_hidden(slice))
+        .map(|(slice, data)| {
+            let mut title = format!("{} {}", name, slice + 1);
+            let mut highlighted = false;
+            match last_move {
+                Some(direction) if direction.axis == slice_axis => {
+                    highlighted = slice == if direction.towards_start { 0 } else { N - 1 };
+                }
+                Some(direction) => {
+                    let arrow = match (direction.axis == column_axis, direction.towards_start) {
+                        (true, true) => "←",
+                        (true, false) => "→",
+                        (false, true) => "↑",
+                        (false, false) => "↓",
+                    };
+                    title = format!("{} {}", title, arrow);
+                }
+                None => {}
+            }
+            Panel { board: theme.clone(), title, highlighted, data }
+        })
+        .collect();
+    if panels.is_empty() {
+        write!(out, "    every {} is hidden, 1-{} to show them\r\n", name.to_lowercase(), N)?;
+    } else {
+        board::Board::print_side_by_side(&panels, N, &*game.rules().merge, out)?;
+    }
+    footer(out, status, CUBE_KEYS)?;
     Ok(out)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::Notation;
+    use crate::rules::Rules;
+
+    // A game that just made the target tile
+    fn won<G: GameState>(position: &str) -> G {
+        let game = G::from_notation(&position.parse::<Notation>().unwrap(), 0, Rules::default().with_target(4)).unwrap();
+        let won = game
+            .legal_moves()
+            .iter()
+            .map(|direction| {
+                let mut game = game.clone();
+                game.play_turn(direction);
+                game
+            })
+            .find(|game| *game.status() == GameStatus::Won);
+        won.expect("a move merges the two 2s")
+    }
+
+    #[test]
+    fn draws_won_games() {
+        let mut out = Vec::new();
+        display_game(&mut out, &board::Board::new(), &won::<game::Game<4>>("4x4:1,1,0,0/0,0,0,0/0,0,0,0/0,0,0,0 player 0")).unwrap();
+        assert!(!out.is_empty());
+
+        let mut out = Vec::new();
+        let cube = won::<ThreeDGame<2>>("2x2x2:1,1/0,0|0,0/0,0 player 0");
+        display_cube(&mut out, &board::Board::new(), &cube, &CubeView::default(), None).unwrap();
+        assert!(!out.is_empty());
+    }
+
+    #[test]
+    fn hidden_slices_are_left_out() {
+        let cube = ThreeDGame::<2>::from_notation(&"2x2x2:1,0/0,0|0,0/0,2".parse().unwrap(), 0, Rules::default()).unwrap();
+        let draw = |view: &CubeView| {
+            let mut out = Vec::new();
+            display_cube(&mut out, &board::Board::new(), &cube, view, None).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        let mut view = CubeView::default();
+        view.toggle(1);
+        assert!(view.is_hidden(1) && !view.is_hidden(0));
+        let drawn = draw(&view);
+        assert!(drawn.contains("Layer 1") && !drawn.contains("Layer 2"));
+        view.rotate();
+        let drawn = draw(&view);
+        assert!(drawn.contains("Column 1") && !drawn.contains("Column 2"));
+
+        view.toggle(0);
+        assert!(draw(&view).contains("every column is hidden"));
+        view.toggle(1);
+        assert!(draw(&view).contains("Column 2"));
+    }
+}
@@ -0,0 +1,566 @@
+use crate::algorithm::{self, TileMove};
+use crate::bitboard::Bitboard;
+use crate::bonus::TileType;
+use crate::game::{GameStatus, TurnOutcome};
+use crate::history::{History, Snapshot, Turn};
+use crate::moves::{Move, MoveSet};
+use crate::notation::{Notation, Side};
+use crate::rules::Rules;
+use crate::state::GameState;
+use crate::zobrist;
+use rand::{prelude::SliceRandom, SeedableRng};
+use rand_xoshiro::Xoshiro256Plus;
+use std::fmt::Debug;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::sync::Arc;
+use strum::IntoEnumIterator;
+
+/// A move along one axis of a board: every line along `axis` slides towards its first cell, or towards its last one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct AxisMove {
+    pub axis: usize,
+    pub towards_start: bool,
+}
+
+/// The lines of a board along one of its axes.
+#[derive(Clone, Copy, Debug)]
+pub struct Lines {
+    // Cells in a line
+    pub length: usize,
+    // Distance between two neighbouring cells of a line
+    pub stride: usize,
+    // Cells of the board
+    cells: usize,
+}
+
+impl Lines {
+    /// The lines along `axis` of a board of `shape`, its outermost axis first and its last one varying fastest.
+    pub fn along(shape: &[usize], axis: usize) -> Self {
+        Lines {
+            length: shape[axis],
+            stride: shape[axis + 1..].iter().product(),
+            cells: shape.iter().product(),
+        }
+    }
+
+    /// The first cell of every line.
+    pub fn starts(self) -> impl Iterator<Item = usize> {
+        let Lines { length, stride, cells } = self;
+        (0..cells / length).map(move |line| line / stride * stride * length + line % stride)
+    }
+
+    /// The cells of the line starting at `start`, in order.
+    pub fn cells(self, start: usize) -> impl DoubleEndedIterator<Item = usize> {
+        (0..self.length).map(move |t| start + t * self.stride)
+    }
+}
+
+/// The tiles of a board stored inline, one byte per cell: the exponents and the typed tiles all fit.
+/// Cloning a board copies them, without allocating.
+pub trait Cells: Clone + Eq + Hash + Debug {
+    /// A board of `cells` empty cells.
+    fn empty(cells: usize) -> Self;
+    /// The tiles with the last axis varying fastest.
+    fn tiles(&self) -> &[u8];
+    fn tiles_mut(&mut self) -> &mut [u8];
+}
+
+// The rows of a 2D board
+impl<const R: usize, const C: usize> Cells for [[u8; C]; R] {
+    fn empty(_cells: usize) -> Self {
+        [[0; C]; R]
+    }
+    fn tiles(&self) -> &[u8] {
+        self.as_flattened()
+    }
+    fn tiles_mut(&mut self) -> &mut [u8] {
+        self.as_flattened_mut()
+    }
+}
+
+// The layers of a cube
+impl<const N: usize> Cells for [[[u8; N]; N]; N] {
+    fn empty(_cells: usize) -> Self {
+        [[[0; N]; N]; N]
+    }
+    fn tiles(&self) -> &[u8] {
+        self.as_flattened().as_flattened()
+    }
+    fn tiles_mut(&mut self) -> &mut [u8] {
+        self.as_flattened_mut().as_flattened_mut()
+    }
+}
+
+// The boards too large to be copied around, on the heap
+impl Cells for Box<[u8]> {
+    fn empty(cells: usize) -> Self {
+        vec![0; cells].into_boxed_slice()
+    }
+    fn tiles(&self) -> &[u8] {
+        self
+    }
+    fn tiles_mut(&mut self) -> &mut [u8] {
+        self
+    }
+}
+
+/// The shape of a board and the names of its moves, what makes an `Engine` a `Game`, a `ThreeDGame` or a `HyperGame`.
+pub trait Geometry: Clone {
+    type Move: Move + Debug;
+    /// How the tiles are stored, `SHAPE` as nested arrays where it can be.
+    type Cells: Cells;
+
+    /// Length of every axis, see `GameState::SHAPE`.
+    const SHAPE: &'static [usize];
+
+    /// The axis a move slides the lines along, and towards which end.
+    fn axis_move(direction: Self::Move) -> AxisMove;
+
+    /// The board packed in a `Bitboard` for the moves of the search, when it fits in one.
+    /// Only asked when the merge rule `doubles`, None to slide the lines one by one.
+    fn pack(_data: &[u8]) -> Option<Bitboard> {
+        None
+    }
+
+    /// See `GameState::get_tiles_snake_sum`, by default `Engine::get_tiles_snake_sum_path`.
+    fn snake_sum(game: &Engine<Self>) -> f64 {
+        game.get_tiles_snake_sum_path()
+    }
+}
+
+/// A 2048 game on a board of any number of dimensions: the state, the turns and their history.
+/// Every move slides the lines along one axis of the board, see `Geometry`.
+#[derive(Clone)]
+pub struct Engine<G: Geometry> {
+    pub(crate) status: GameStatus,
+    pub(crate) already_won: bool,
+    // Sum of the values of all the tiles made by merges
+    pub(crate) score: u64,
+    // Sum of their exponents, what the score used to be
+    pub(crate) exponent_score: i32,
+    // The tiles, the last axis varying fastest, only written along with `hash`
+    pub(crate) data: G::Cells,
+    // The Zobrist hash of the tiles, see `zobrist`
+    pub(crate) hash: u64,
+    // The seed the game was created with, and the generator every spawn is drawn from
+    pub(crate) seed: u64,
+    pub(crate) rng: Xoshiro256Plus,
+    // Shared by the copies of the game the search makes
+    pub(crate) rules: Arc<Rules>,
+    // Turns that can be undone, only kept once enabled with `with_history`
+    pub(crate) history: Option<History<G::Move>>,
+    pub(crate) geometry: PhantomData<G>,
+}
+
+// Two games are the same position regardless of their score and of where their random generators are:
+// the score doesn't change which moves are best, which is also why `TranspositionTable` keys by the tiles alone.
+impl<G: Geometry> PartialEq for Engine<G> {
+    fn eq(&self, other: &Self) -> bool {
+        self.status == other.status && self.already_won == other.already_won && self.data == other.data
+    }
+}
+
+impl<G: Geometry> Eq for Engine<G> {}
+
+impl<G: Geometry> Hash for Engine<G> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.status.hash(state);
+        self.already_won.hash(state);
+        // Equal games have the same tiles, so the same Zobrist hash
+        self.hash.hash(state);
+    }
+}
+
+impl<G: Geometry> Engine<G> {
+    // Slides every line along the axis of the move, adding to `trace` where every tile went when it is given
+    fn slide(&mut self, direction: AxisMove, mut trace: Option<&mut Vec<TileMove>>) -> bool {
+        if self.rules.bonus.is_some() {
+            remove_bonused(self.data.tiles_mut(), &mut self.hash);
+        }
+        // The search needs no trace, and goes through the packed board when there is one
+        if trace.is_none() && self.rules.merge.doubles() {
+            if let Some(packed) = G::pack(self.data.tiles()).and_then(|board| board.slide(direction)) {
+                let tiles = self.data.tiles_mut();
+                for cell in 0..tiles.len() {
+                    let tile = packed.board.tile(cell);
+                    if tiles[cell] != tile {
+                        zobrist::set(tiles, &mut self.hash, cell, tile);
+                    }
+                }
+                let (mut score, mut exponent_score, mut won) = (0u64, 0, false);
+                for tile in packed.merged.iter().take_while(|&&tile| tile != 0).map(|&tile| tile as i32) {
+                    score = score.saturating_add(self.rules.merge.value(tile));
+                    exponent_score += tile;
+                    won |= self.rules.wins(tile);
+                }
+                self.add_merges(score, exponent_score, won);
+                return packed.moved;
+            }
+        }
+        let lines = Lines::along(G::SHAPE, direction.axis);
+        let mut mutated = false;
+        let mut score = 0u64;
+        let mut exponent_score = 0;
+        let mut won = false;
+        let merge = &*self.rules.merge;
+        let tiles = self.data.tiles_mut();
+        for start in lines.starts() {
+            let slide = algorithm::slide_line(
+                tiles,
+                &mut self.hash,
+                lines,
+                start,
+                direction.towards_start,
+                merge,
+                trace.as_deref_mut(),
+            );
+            if self.rules.wins(slide.max_merged) {
+                won = true;
+            }
+            score = score.saturating_add(slide.score);
+            exponent_score += slide.exponent_score;
+            mutated |= slide.is_moving;
+        }
+
+        self.add_merges(score, exponent_score, won);
+        mutated
+    }
+
+    // Counts the merges of a slide, and whether they made the target tile
+    fn add_merges(&mut self, score: u64, exponent_score: i32, won: bool) {
+        self.score = self.score.saturating_add(score);
+        self.exponent_score += exponent_score;
+        if won && !self.already_won {
+            self.status = GameStatus::Won;
+            self.already_won = true;
+        }
+    }
+
+    // `play_turn`, keeping the moves that can be redone
+    fn turn(&mut self, direction: G::Move) -> TurnOutcome {
+        let mut outcome = TurnOutcome::default();
+        if matches!(self.status, GameStatus::Lost | GameStatus::Interrupted) || !self.legal_moves().contains(direction) {
+            return outcome;
+        }
+        let before = self.history.is_some().then(|| self.snapshot());
+        self.go_on();
+
+        let (score, already_won) = (self.score, self.already_won);
+        outcome.moves = self.traced_movement(&direction);
+        outcome.moved = true;
+        outcome.score = self.score - score;
+        outcome.won = self.already_won && !already_won;
+
+        outcome.spawned = self.spawn();
+        if self.check_if_lost() {
+            self.lose();
+            outcome.lost = true;
+        }
+        if let (Some(history), Some(before)) = (&mut self.history, before) {
+            history.record(Turn {
+                direction,
+                score: outcome.score,
+                spawned: outcome.spawned.clone(),
+                before,
+            });
+        }
+        outcome
+    }
+
+    /// Keeps the last `limit` turns, to undo and redo them.
+    pub fn with_history(mut self, limit: usize) -> Self {
+        self.history = Some(History::new(limit));
+        self
+    }
+    pub fn history(&self) -> Option<&History<G::Move>> {
+        self.history.as_ref()
+    }
+    /// Goes back to before the last turn, returns whether there was one to undo.
+    pub fn undo(&mut self) -> bool {
+        match self.history.as_mut().and_then(History::undo) {
+            Some(before) => {
+                self.restore(before);
+                true
+            }
+            None => false,
+        }
+    }
+    /// Plays the last undone move again, with the same spawns as the first time.
+    pub fn redo(&mut self) -> bool {
+        match self.history.as_mut().and_then(History::redo) {
+            Some(direction) => self.turn(direction).moved,
+            None => false,
+        }
+    }
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            data: self.data.tiles().to_vec(),
+            score: self.score,
+            exponent_score: self.exponent_score,
+            status: self.status.clone(),
+            already_won: self.already_won,
+            rng: self.rng.clone(),
+        }
+    }
+    fn restore(&mut self, snapshot: Snapshot) {
+        self.data.tiles_mut().copy_from_slice(&snapshot.data);
+        self.hash = zobrist::hash(&snapshot.data);
+        self.score = snapshot.score;
+        self.exponent_score = snapshot.exponent_score;
+        self.status = snapshot.status;
+        self.already_won = snapshot.already_won;
+        self.rng = snapshot.rng;
+    }
+
+    /// Weights halving along a snake through the board from its first cell: back and forth along the last axis,
+    /// then along the one before it, and so on, like the rows of the 2D snake.
+    /// Past `SNAKE_SPAN` cells the weights shrink by less than half, so that the first one stays finite.
+    pub fn get_tiles_snake_sum_path(&self) -> f64 {
+        let tiles = self.data.tiles();
+        let mut sum = 0.0;
+        let span = (tiles.len() - 1).min(SNAKE_SPAN);
+        let ratio = 2.0f64.powf(span as f64 / (tiles.len() - 1).max(1) as f64);
+        let mut weight = 2.0f64.powi(span as i32);
+        for step in 0..tiles.len() {
+            let mut cell = 0;
+            let mut inner = tiles.len();
+            for &length in G::SHAPE {
+                let outer = inner;
+                inner /= length;
+                // Every other pass along an axis goes backwards
+                let t = step / inner % length;
+                let t = if (step / outer).is_multiple_of(2) { t } else { length - 1 - t };
+                cell += t * inner;
+            }
+            sum += self.rules.merge.value(tiles[cell] as i32) as f64 * weight;
+            weight /= ratio;
+        }
+        sum
+    }
+}
+
+// The first snake weight is at most 2^SNAKE_SPAN: times the 4096 cells of the largest board
+// and tiles of up to 2^64, the sum stays below the largest f64
+const SNAKE_SPAN: usize = 900;
+
+// The bonused tiles of the last move leave the board when the next one is played, see `TileType`
+fn remove_bonused(data: &mut [u8], hash: &mut u64) {
+    for cell in 0..data.len() {
+        if TileType::of(data[cell] as i32) == TileType::Bonused {
+            zobrist::set(data, hash, cell, 0);
+        }
+    }
+}
+
+impl<G: Geometry> GameState for Engine<G> {
+    type Move = G::Move;
+
+    const SHAPE: &'static [usize] = G::SHAPE;
+
+    fn with_rules(seed: u64, rules: Rules) -> Self {
+        let mut rng = Xoshiro256Plus::seed_from_u64(seed);
+        let mut data = G::Cells::empty(G::SHAPE.iter().product());
+        let tiles = data.tiles_mut();
+        tiles[0] = rules.spawn.initial_value() as u8;
+        tiles[1] = rules.spawn.initial_value() as u8;
+        tiles.shuffle(&mut rng);
+        Engine {
+            status: GameStatus::Ongoing,
+            already_won: false,
+            score: 0,
+            exponent_score: 0,
+            hash: zobrist::hash(data.tiles()),
+            data,
+            seed,
+            rng,
+            rules: Arc::new(rules),
+            history: None,
+            geometry: PhantomData,
+        }
+    }
+    fn from_notation(notation: &Notation, seed: u64, rules: Rules) -> std::result::Result<Self, String> {
+        notation.expect_shape(G::SHAPE)?;
+        if rules.bonus.is_none() && notation.tiles.iter().any(|&tile| TileType::of(tile) != TileType::Number) {
+            return Err("the position has bonus tiles, they need the bonus rules".to_string());
+        }
+        let mut game = Self::with_rules(seed, rules);
+        for (cell, &tile) in game.data.tiles_mut().iter_mut().zip(&notation.tiles) {
+            *cell = tile as u8;
+        }
+        game.hash = zobrist::hash(game.data.tiles());
+        game.score = notation.score;
+        game.already_won = notation.tiles.iter().any(|&tile| game.rules.wins(tile));
+        if notation.side == Side::Spawn {
+            game.spawn();
+        }
+        if game.check_if_lost() {
+            game.lose();
+        }
+        Ok(game)
+    }
+    fn seed(&self) -> u64 {
+        self.seed
+    }
+    fn rules(&self) -> &Rules {
+        &self.rules
+    }
+    fn data(&self) -> &[u8] {
+        self.data.tiles()
+    }
+    fn zobrist(&self) -> u64 {
+        self.hash
+    }
+    fn score(&self) -> u64 {
+        self.score
+    }
+    fn exponent_score(&self) -> i32 {
+        self.exponent_score
+    }
+    fn status(&self) -> &GameStatus {
+        &self.status
+    }
+    fn already_won(&self) -> bool {
+        self.already_won
+    }
+    fn interrupt(&mut self) {
+        self.status = GameStatus::Interrupted;
+    }
+    fn go_on(&mut self) {
+        self.status = GameStatus::Ongoing;
+    }
+    fn lose(&mut self) {
+        self.status = GameStatus::Lost;
+    }
+    fn legal_moves(&self) -> MoveSet<G::Move> {
+        let merge = &*self.rules.merge;
+        // The bonused tiles are gone by the time the tiles slide
+        let mut cleared = None;
+        if self.rules.bonus.is_some() && self.data().iter().any(|&tile| TileType::of(tile as i32) == TileType::Bonused) {
+            let mut data = self.data.clone();
+            remove_bonused(data.tiles_mut(), &mut 0);
+            cleared = Some(data);
+        }
+        let data = cleared.as_ref().unwrap_or(&self.data).tiles();
+        let packed = if self.rules.merge.doubles() { G::pack(data) } else { None };
+        G::Move::iter()
+            .filter(|&direction| {
+                let direction = G::axis_move(direction);
+                if let Some(board) = packed {
+                    return board.can_slide(direction);
+                }
+                let lines = Lines::along(G::SHAPE, direction.axis);
+                algorithm::can_slide_along(data, lines.length, lines.stride, direction.towards_start, merge)
+            })
+            .collect()
+    }
+    fn movement(&mut self, direction: &G::Move) -> bool {
+        self.slide(G::axis_move(*direction), None)
+    }
+    fn traced_movement(&mut self, direction: &G::Move) -> Vec<TileMove> {
+        let mut trace = Vec::new();
+        if !self.slide(G::axis_move(*direction), Some(&mut trace)) {
+            trace.clear();
+        }
+        trace
+    }
+    fn play_turn(&mut self, direction: G::Move) -> TurnOutcome {
+        let outcome = self.turn(direction);
+        if let (true, Some(history)) = (outcome.moved, &mut self.history) {
+            history.forget_undone();
+        }
+        outcome
+    }
+    fn new_tile(&mut self, position: usize, value: i32) {
+        zobrist::set(self.data.tiles_mut(), &mut self.hash, position, value as u8);
+    }
+    fn rng(&mut self) -> &mut Xoshiro256Plus {
+        &mut self.rng
+    }
+    fn get_tiles_snake_sum(&self) -> f64 {
+        G::snake_sum(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{Direction, Game};
+
+    fn game<const R: usize, const C: usize>(position: &str) -> Game<R, C> {
+        Game::from_notation(&position.parse().unwrap(), 0, Rules::default()).unwrap()
+    }
+
+    fn moves<G: Geometry>(game: &Engine<G>) -> Vec<G::Move> {
+        G::Move::iter().filter(|&direction| game.legal_moves().contains(direction)).collect()
+    }
+
+    #[test]
+    fn tiles_against_the_wall_cannot_slide_into_it() {
+        // The packed 4x4 board and the generic 3x3 one
+        let packed = game::<4, 4>("4x4:1,2,0,0/0,0,0,0/0,0,0,0/0,0,0,0");
+        assert_eq!(moves(&packed), [Direction::Down, Direction::Right]);
+        let generic = game::<3, 3>("3x3:1,2,0/0,0,0/0,0,0");
+        assert_eq!(moves(&generic), [Direction::Down, Direction::Right]);
+    }
+
+    #[test]
+    fn a_full_board_without_merges_has_no_legal_moves() {
+        let packed = game::<4, 4>("4x4:1,2,1,2/2,1,2,1/1,2,1,2/2,1,2,1");
+        assert!(packed.legal_moves().is_empty());
+        assert!(packed.check_if_lost());
+        let generic = game::<3, 3>("3x3:1,2,1/2,1,2/1,2,1");
+        assert!(generic.legal_moves().is_empty());
+        // One merge is enough for both moves along its axis
+        let merging = game::<3, 3>("3x3:1,1,2/2,3,1/1,2,3");
+        assert_eq!(moves(&merging), [Direction::Left, Direction::Right]);
+    }
+
+    #[test]
+    fn a_move_that_changes_nothing_is_not_a_turn() {
+        let mut game = game::<4, 4>("4x4:1,2,0,0/0,0,0,0/0,0,0,0/0,0,0,0");
+        let outcome = game.play_turn(Direction::Left);
+        assert!(!outcome.moved);
+        assert_eq!((outcome.score, outcome.spawned.len(), outcome.moves.len()), (0, 0, 0));
+        assert_eq!(game.notation().to_string(), "4x4:1,2,0,0/0,0,0,0/0,0,0,0/0,0,0,0 player 0");
+    }
+
+    #[test]
+    fn only_the_first_target_tile_wins() {
+        let mut game = game::<4, 4>("4x4:10,10,0,0/0,0,0,0/10,0,0,0/10,0,0,0");
+        let outcome = game.play_turn(Direction::Left);
+        assert!(outcome.won);
+        assert_eq!((outcome.score, outcome.spawned.len()), (2048, 1));
+        assert_eq!(*game.status(), GameStatus::Won);
+        // The two 1024s of the first column make another 2048
+        let outcome = game.play_turn(Direction::Up);
+        assert_eq!(outcome.score, 2048);
+        assert!(!outcome.won);
+        assert_eq!(*game.status(), GameStatus::Ongoing);
+    }
+
+    #[test]
+    fn the_turn_leaving_no_move_loses() {
+        let mut game = game::<2, 2>("2x2:3,4/0,5");
+        let outcome = game.play_turn(Direction::Left);
+        assert!(outcome.moved && outcome.lost);
+        // The spawn fills the last cell, next to a 16 and a 32
+        assert_eq!(outcome.spawned.len(), 1);
+        assert_eq!(*game.status(), GameStatus::Lost);
+        assert!(!game.play_turn(Direction::Right).moved);
+    }
+
+    #[test]
+    fn positions_are_equal_whatever_their_score() {
+        let hash = |game: &Game<4>| {
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            game.hash(&mut hasher);
+            hasher.finish()
+        };
+        let low = game::<4, 4>("4x4:1,2,0,0/0,0,0,0/0,0,0,0/0,0,0,0 player 4");
+        let high = game::<4, 4>("4x4:1,2,0,0/0,0,0,0/0,0,0,0/0,0,0,0 player 4096");
+        assert!(low == high);
+        assert_eq!(hash(&low), hash(&high));
+        assert!(low != game::<4, 4>("4x4:2,1,0,0/0,0,0,0/0,0,0,0/0,0,0,0 player 4"));
+    }
+}
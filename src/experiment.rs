@@ -0,0 +1,288 @@
+use crate::game::GameStatus;
+use crate::state::GameState;
+use crate::strategies::strategy::Strategy;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::{BufRead, Error, ErrorKind, Result, Write};
+
+// The tiles we track the first occurrence of, in the order of the CSV columns
+pub const TRACKED_TILES: [u64; 17] = [
+    2, 4, 8, 16, 32, 64, 128, 256, 512, 1024, 2048, 4096, 8192, 16384, 32768, 65536, 131072,
+];
+
+// Files written before the score was the sum of the merged tile values have no such column,
+// and their "Score" column holds the sum of the merged exponents.
+const EXPONENT_SCORE_COLUMN: &str = "Exponent Score";
+
+/// The result of a single game played by a strategy, one row of the results CSV.
+#[derive(Clone, Debug, Default)]
+pub struct GameRecord {
+    pub iterations: usize,
+    pub max_tile: u64,
+    // The standard 2048 score, `None` for legacy results which only have the exponent score
+    pub score: Option<u64>,
+    // The sum of the exponents of the merged tiles
+    pub exponent_score: Option<i64>,
+    // The seed the game was created with, `None` for results written before seeds were recorded
+    pub seed: Option<u64>,
+    // The iteration at which each tile value first became the max tile
+    pub first_occurrence: HashMap<u64, usize>,
+}
+
+fn optional<T: fmt::Display>(value: Option<T>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+impl GameRecord {
+    pub fn write_csv_header<W: Write>(out: &mut W) -> Result<()> {
+        writeln!(out, "Game Iterations,Max Tile,Score,2,4,8,16,32,64,128,256,512,1024,2048,4096,8192, 16384, 32768,65536,131072,Seed,{}", EXPONENT_SCORE_COLUMN)
+    }
+
+    pub fn write_csv_row<W: Write>(&self, out: &mut W) -> Result<()> {
+        write!(out, "{},{},{}", self.iterations, self.max_tile, optional(self.score))?;
+        for tile in TRACKED_TILES {
+            write!(out, ",{}", self.first_occurrence.get(&tile).unwrap_or(&0))?;
+        }
+        writeln!(out, ",{},{}", optional(self.seed), optional(self.exponent_score))
+    }
+
+    /// Reads a results CSV, either as written by `write_csv_row` or by older versions
+    /// whose score is the sum of the merged exponents.
+    pub fn read_csv<R: BufRead>(input: R) -> Result<Vec<GameRecord>> {
+        let mut lines = input.lines();
+        let legacy = match lines.next() {
+            Some(header) => !header?.split(',').any(|column| column.trim() == EXPONENT_SCORE_COLUMN),
+            None => return Ok(Vec::new()),
+        };
+
+        let mut records = Vec::new();
+        for (line_number, line) in lines.enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let invalid = |what: &str| {
+                Error::new(ErrorKind::InvalidData, format!("line {}: invalid {}", line_number + 2, what))
+            };
+            let columns: Vec<&str> = line.split(',').map(str::trim).collect();
+            let column = |index: usize| columns.get(index).copied().filter(|column| !column.is_empty());
+
+            let mut record = GameRecord {
+                iterations: column(0).and_then(|c| c.parse().ok()).ok_or_else(|| invalid("iterations"))?,
+                max_tile: column(1).and_then(|c| c.parse().ok()).ok_or_else(|| invalid("max tile"))?,
+                seed: column(3 + TRACKED_TILES.len()).and_then(|c| c.parse().ok()),
+                ..GameRecord::default()
+            };
+            let score = column(2).map(|c| c.parse::<i64>().map_err(|_| invalid("score"))).transpose()?;
+            if legacy {
+                record.exponent_score = score;
+            } else {
+                record.score = score.map(|score| score as u64);
+                record.exponent_score = column(4 + TRACKED_TILES.len()).and_then(|c| c.parse().ok());
+            }
+            // 0 is written both for tiles never reached and tiles reached on the first move
+            for (i, tile) in TRACKED_TILES.iter().enumerate() {
+                if let Some(iteration) = column(3 + i).and_then(|c| c.parse().ok()).filter(|&iteration| iteration > 0) {
+                    record.first_occurrence.insert(*tile, iteration);
+                }
+            }
+            records.push(record);
+        }
+        Ok(records)
+    }
+}
+
+/// Plays `game` until the end with the given strategy.
+/// `on_move` is called with the initial board, then after every move with the move just played, e.g. to display the board.
+pub fn play_game<G, S, F>(mut game: G, strategy: &mut S, mut on_move: F) -> Result<(G, GameRecord)>
+where
+    G: GameState,
+    S: Strategy<G>,
+    F: FnMut(&G, Option<G::Move>) -> Result<()>,
+{
+    let mut record = GameRecord {
+        seed: Some(game.seed()),
+        ..GameRecord::default()
+    };
+    on_move(&game, None)?;
+
+    // The AI always keeps playing after reaching the target, `play_turn` goes on from a won game
+    while *game.status() != GameStatus::Lost {
+        let best_move: Option<G::Move> = strategy.calculate_next_move(&game);
+        match best_move {
+            Some(best_move) => {
+                if !game.play_turn(best_move).moved {
+                    break;
+                }
+
+                let (_, max_tile) = game.get_max_tile();
+                record.first_occurrence.entry(max_tile).or_insert(record.iterations);
+
+                on_move(&game, Some(best_move))?;
+            }
+            None => {
+                break;
+            }
+        }
+        record.iterations += 1;
+    }
+
+    let (_, max_tile) = game.get_max_tile();
+    record.max_tile = max_tile;
+    record.score = Some(game.score());
+    record.exponent_score = Some(game.exponent_score() as i64);
+    Ok((game, record))
+}
+
+/// Aggregated statistics over the rows of a results CSV.
+#[derive(Debug, Default)]
+pub struct Summary {
+    pub games: usize,
+    pub total_iterations: usize,
+    // Only over the games that have a score, see `GameRecord::score`
+    pub scored_games: usize,
+    pub total_score: u64,
+    pub best_score: Option<u64>,
+    // Seed of the game with the best score, to replay it
+    pub best_seed: Option<u64>,
+    pub exponent_scored_games: usize,
+    pub total_exponent_score: i64,
+    pub max_tiles: BTreeMap<u64, usize>,
+}
+
+impl Summary {
+    pub fn add(&mut self, record: &GameRecord) {
+        self.games += 1;
+        self.total_iterations += record.iterations;
+        if let Some(score) = record.score {
+            self.scored_games += 1;
+            self.total_score += score;
+            if self.best_score.is_none_or(|best| score > best) {
+                self.best_score = Some(score);
+                self.best_seed = record.seed;
+            }
+        }
+        if let Some(exponent_score) = record.exponent_score {
+            self.exponent_scored_games += 1;
+            self.total_exponent_score += exponent_score;
+        }
+        *self.max_tiles.entry(record.max_tile).or_insert(0) += 1;
+    }
+
+    pub fn from_csv<R: BufRead>(input: R) -> Result<Self> {
+        let mut summary = Summary::default();
+        for record in GameRecord::read_csv(input)? {
+            summary.add(&record);
+        }
+        Ok(summary)
+    }
+
+    pub fn mean_score(&self) -> f64 {
+        self.total_score as f64 / self.scored_games.max(1) as f64
+    }
+
+    pub fn mean_exponent_score(&self) -> f64 {
+        self.total_exponent_score as f64 / self.exponent_scored_games.max(1) as f64
+    }
+
+    pub fn mean_iterations(&self) -> f64 {
+        self.total_iterations as f64 / self.games.max(1) as f64
+    }
+
+    // Share of the games whose max tile is at least `tile`
+    pub fn reached(&self, tile: u64) -> f64 {
+        let count: usize = self.max_tiles.range(tile..).map(|(_, count)| count).sum();
+        count as f64 / self.games.max(1) as f64
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "games:           {}", self.games)?;
+        if self.scored_games > 0 {
+            writeln!(f, "mean score:      {:.1}", self.mean_score())?;
+        }
+        match (self.best_score, self.best_seed) {
+            (Some(score), Some(seed)) => writeln!(f, "best score:      {} (seed {})", score, seed)?,
+            (Some(score), None) => writeln!(f, "best score:      {}", score)?,
+            _ => {}
+        }
+        if self.exponent_scored_games > 0 {
+            writeln!(f, "mean exp. score: {:.1}", self.mean_exponent_score())?;
+        }
+        if self.scored_games < self.games {
+            writeln!(f, "({} games only have the legacy exponent score, their tile value score is unknown)", self.games - self.scored_games)?;
+        }
+        writeln!(f, "mean iterations: {:.1}", self.mean_iterations())?;
+        writeln!(f, "max tile    games   reached")?;
+        for (tile, count) in &self.max_tiles {
+            writeln!(f, "{:>8} {:>8} {:>8.1}%", tile, count, 100.0 * self.reached(*tile))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{Direction, Game};
+    use crate::rules::Rules;
+
+    const LEGACY: &str = "Game Iterations,Max Tile,Score,2,4,8,16,32,64,128,256,512,1024,2048,4096,8192, 16384, 32768,65536,131072\n\
+                          812,512,3421,0,1,3,9,21,36,72,131,249,0,0,0,0,0,0,0,0\n";
+
+    #[test]
+    fn legacy_rows_only_have_the_exponent_score() {
+        let records = GameRecord::read_csv(LEGACY.as_bytes()).unwrap();
+        assert_eq!(records.len(), 1);
+        let record = &records[0];
+        assert_eq!((record.iterations, record.max_tile), (812, 512));
+        assert_eq!(record.score, None);
+        assert_eq!(record.exponent_score, Some(3421));
+        assert_eq!(record.seed, None);
+        // The 2 and the tiles never reached are written as 0
+        assert_eq!(record.first_occurrence.get(&2), None);
+        assert_eq!(record.first_occurrence.get(&4), Some(&1));
+        assert_eq!(record.first_occurrence.get(&512), Some(&249));
+        assert_eq!(record.first_occurrence.get(&1024), None);
+    }
+
+    #[test]
+    fn current_rows_keep_both_scores_and_the_seed() {
+        let mut record = GameRecord {
+            iterations: 1493,
+            max_tile: 2048,
+            score: Some(28232),
+            exponent_score: Some(4390),
+            seed: Some(7),
+            ..GameRecord::default()
+        };
+        record.first_occurrence.insert(2048, 1490);
+        let mut csv = Vec::new();
+        GameRecord::write_csv_header(&mut csv).unwrap();
+        record.write_csv_row(&mut csv).unwrap();
+
+        let read = &GameRecord::read_csv(csv.as_slice()).unwrap()[0];
+        assert_eq!((read.iterations, read.max_tile), (1493, 2048));
+        assert_eq!((read.score, read.exponent_score, read.seed), (Some(28232), Some(4390), Some(7)));
+        assert_eq!(read.first_occurrence, record.first_occurrence);
+    }
+
+    #[test]
+    fn score_sums_the_values_and_exponent_score_the_exponents() {
+        let notation = "4x4:1,1,2,2/3,3,0,0/0,0,0,0/0,0,0,0".parse().unwrap();
+        let mut game = Game::<4>::from_notation(&notation, 0, Rules::default()).unwrap();
+        assert!(game.movement(&Direction::Left));
+        // 2+2 into a 4, 4+4 into an 8 and 8+8 into a 16
+        assert_eq!(game.score(), 4 + 8 + 16);
+        assert_eq!(game.exponent_score(), 2 + 3 + 4);
+
+        let mut summary = Summary::default();
+        summary.add(&GameRecord {
+            score: Some(game.score()),
+            exponent_score: Some(game.exponent_score() as i64),
+            ..GameRecord::default()
+        });
+        assert_eq!((summary.mean_score(), summary.mean_exponent_score()), (28.0, 9.0));
+    }
+}
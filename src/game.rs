@@ -1,17 +1,15 @@
 use strum_macros::EnumIter;
-use rand::prelude::*;
-use crate::algorithm;
-use rand::distributions::WeightedIndex;
-use std::fs::File;
-use std::io::{BufWriter, Result, Write};
-
-use rand::{
-    prelude::{IteratorRandom, SliceRandom},
-    Rng, SeedableRng,
-};
-use rand_xoshiro::Xoshiro256Plus;
-
-#[derive(Clone, EnumIter, Debug, PartialEq)]
+use crate::algorithm::{self, TileMove};
+use crate::bitboard::Bitboard;
+use crate::engine::{AxisMove, Cells, Engine, Geometry};
+use crate::moves::Move;
+use crate::rules::Rules;
+use crate::state::GameState;
+use crate::symmetry::Symmetry;
+use crate::zobrist;
+use std::marker::PhantomData;
+
+#[derive(Clone, Copy, EnumIter, Debug, PartialEq, Eq)]
 pub enum Direction {
     Up,
     Down,
@@ -19,7 +17,13 @@ pub enum Direction {
     Right,
 }
 
-#[derive(Clone, PartialEq, EnumIter, Eq, Hash)]
+impl Move for Direction {
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, EnumIter, Eq, Hash)]
 pub enum GameStatus {
     Ongoing,
     Won,
@@ -27,204 +31,136 @@ pub enum GameStatus {
     Interrupted,
 }
 
-#[derive(Clone, Eq, Hash, PartialEq)]
-pub struct Game<const N: usize> {
-    status: GameStatus,
-    already_won: bool,
-    score: i32,
-    pub data: Vec<i32>,
-    dimention: i32,
+/// What happened during one turn, see `Game::play_turn`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TurnOutcome {
+    // Whether the move changed the board, nothing else happens when it didn't
+    pub moved: bool,
+    // Score gained by the merges of the move
+    pub score: u64,
+    // Where every tile went, see `Game::traced_movement`
+    pub moves: Vec<TileMove>,
+    // The tiles spawned after the move, as (position, tile)
+    pub spawned: Vec<(usize, i32)>,
+    // The move made the target tile for the first time
+    pub won: bool,
+    // No move is left after the spawn
+    pub lost: bool,
 }
 
-impl <const N: usize> Game<N> {
-    pub fn new() -> Self {
-        let mut rng = Xoshiro256Plus::from_entropy();
-        let mut data = vec![0; N * N];
-        data[0] = 1;
-        data[1] = 1;
-        data.shuffle(&mut rng);
-        Game {
-            status: GameStatus::Ongoing,
-            already_won: false,
-            score: 0,
-            data,
-            dimention: 4,
+/// The classic game on a board of `R` rows and `C` columns, square by default.
+pub type Game<const R: usize, const C: usize = R> = Engine<Plane<R, C>>;
+
+/// The geometry of `Game`: the rows are the last axis, Left and Right slide along them, Up and Down along the columns.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Plane<const R: usize, const C: usize>;
+
+impl<const R: usize, const C: usize> Geometry for Plane<R, C> {
+    type Move = Direction;
+    type Cells = [[u8; C]; R];
+
+    const SHAPE: &'static [usize] = &[R, C];
+
+    fn axis_move(direction: Direction) -> AxisMove {
+        match direction {
+            Direction::Up => AxisMove { axis: 0, towards_start: true },
+            Direction::Down => AxisMove { axis: 0, towards_start: false },
+            Direction::Left => AxisMove { axis: 1, towards_start: true },
+            Direction::Right => AxisMove { axis: 1, towards_start: false },
         }
     }
-    pub fn data(&self) -> &Vec<i32> {
-        &self.data
-    }
-    pub fn score(&self) -> i32 {
-        self.score
+
+    // The 4x4 board slides as a `Bitboard`
+    fn pack(data: &[u8]) -> Option<Bitboard> {
+        if (R, C) != (4, 4) {
+            return None;
+        }
+        Bitboard::from_tiles(data)
     }
-    pub fn status(&self) -> &GameStatus {
-        &self.status
+
+    // The tuned weights of the small square boards, otherwise the snake of `Engine::get_tiles_snake_sum_path`
+    fn snake_sum(game: &Game<R, C>) -> f64 {
+        match (R, C) {
+            (2, 2) => game.get_tiles_snake_sum_2x2(),
+            (3, 3) => game.get_tiles_snake_sum_3x3(),
+            (4, 4) => game.get_tiles_snake_sum_4x4(),
+            (5, 5) => game.get_tiles_snake_sum_5x5(),
+            _ => game.get_tiles_snake_sum_path(),
+        }
     }
-    pub fn interrupt(&mut self) {
-        self.status = GameStatus::Interrupted;
+}
+
+impl<const R: usize, const C: usize> Default for Game<R, C> {
+    fn default() -> Self {
+        Self::new()
     }
-    pub fn go_on(&mut self) {
-        self.status = GameStatus::Ongoing;
+}
+
+impl<const R: usize, const C: usize> Game<R, C> {
+    /// A new game with a random seed, see `seed()` to replay it.
+    pub fn new() -> Self {
+        Self::with_seed(rand::random())
     }
-    pub fn check_if_lost(&self) -> bool {
-        let mut copy: Game<N> = self.clone();
-        !(copy.right() || copy.left() || copy.up() || copy.down())
+    /// A new game where the initial board and every spawned tile are determined by `seed`.
+    pub fn with_seed(seed: u64) -> Self {
+        Self::with_rules(seed, Rules::default())
     }
 
-    pub fn transpose(&self) -> Self {
-        let mut data = self.data.clone();
-        algorithm::transpose::<N>(&mut data);
-        Game {
+    /// The board mirrored along its diagonal, its rows becoming its columns.
+    pub fn transpose(&self) -> Game<C, R> {
+        let mut data = [[0; R]; C];
+        data.tiles_mut().copy_from_slice(&algorithm::transpose_rectangle(self.data(), R, C));
+        Engine {
             status: GameStatus::Ongoing,
             already_won: self.already_won,
             score: self.score,
+            exponent_score: self.exponent_score,
+            hash: zobrist::hash(data.tiles()),
             data,
-            dimention: self.dimention,
+            seed: self.seed,
+            rng: self.rng.clone(),
+            rules: self.rules.clone(),
+            history: None,
+            geometry: PhantomData,
         }
     }
 
-    fn horizontal(&mut self, dir: Direction) -> bool {
-        let mut mutated = false;
-        let mut score = 0;
-        let mut won = false;
-        self.data.chunks_mut(N).for_each(|row| {
-            let (new_row, new_score, is_moving) = match dir {
-                Direction::Right => algorithm::slide_right(row),
-                Direction::Left => algorithm::slide_left(row),
-                _ => (row.to_vec(), 0, false),
-            };
-            if new_score == 2048 {
-                won = true;
-            }
-            score += new_score;
-            
-            if is_moving == true {
-                mutated = true;
-            }
-
-            for i in 0..N {
-                row[i] = new_row[i];
-            }
-        });
-
-        self.score += score;
-        if won && !self.already_won {
-            self.status = GameStatus::Won;
-            self.already_won = true;
-        }
-        
-        mutated
+    /// The rotations and reflections that keep the shape of the board: 8 on a square board, 4 on the others.
+    pub fn symmetries() -> Vec<Symmetry<2>> {
+        Symmetry::all().into_iter().filter(|symmetry| symmetry.preserves(&[R, C])).collect()
     }
-    fn vertical(&mut self, dir: Direction) -> bool {
-        algorithm::transpose::<N>(&mut self.data);
-        let mutated = match dir {
-            Direction::Up => self.left(),
-            Direction::Down => self.right(),
-            _ => false,
-        };
-        algorithm::transpose::<N>(&mut self.data);
-        mutated
+
+    /// The game turned or mirrored into the canonical form shared by all its `symmetries`, and the symmetry that makes it.
+    /// See `Engine::canonical_among`.
+    pub fn canonical(&self) -> (Self, Symmetry<2>) {
+        self.canonical_among(&Self::symmetries())
     }
+
     pub fn new_tile_xy(&mut self, x: i32, y :i32) {
-        self.data[(x * self.dimention + y) as usize] = 2;
-    }
-    pub fn new_tile(&mut self, position :usize, value: i32) {
-        self.data[position] = value;
+        self.new_tile(x as usize * C + y as usize, 2);
     }
 
     pub fn right(&mut self) -> bool {
-        self.horizontal(Direction::Right)
+        self.movement(&Direction::Right)
     }
     pub fn left(&mut self) -> bool {
-        self.horizontal(Direction::Left)
+        self.movement(&Direction::Left)
     }
     pub fn up(&mut self) -> bool {
-        self.vertical(Direction::Up)
+        self.movement(&Direction::Up)
     }
     pub fn down(&mut self) -> bool {
-        self.vertical(Direction::Down)
+        self.movement(&Direction::Down)
     }
 
-    pub fn movement(&mut self, direction: &Direction) -> bool {
-        match direction {
-            Direction::Up => self.up(),
-            Direction::Left => self.left(),
-            Direction::Right => self.right(),
-            Direction::Down => self.down(),
-            _ => false,
-        }
-    }
-    pub fn get_state(&mut self) -> [[i32; 4]; 6]{
-
-        let mut state : [[i32; 4]; 6] = [[0; 4]; 6];
+    /// The tiles row by row.
+    pub fn get_state(&self) -> [[i32; C]; R] {
+        let mut state = [[0; C]; R];
         for (index, value) in self.data().iter().enumerate() {
-            state[index/4][index%4] = value.clone();
+            state[index / C][index % C] = *value as i32;
         }
         state
     }
-    pub fn get_tiles_sum(&self) -> i32{
-        let mut sum = 0;
-        for value in self.data().iter() {
-            sum += value;
-        }
-        sum
-    }
-    pub fn get_max_tile(&self) -> (usize, i32){
-        let mut max = 0;
-        let mut index = 0;
-        for (i, value) in self.data().iter().enumerate() {
-            if value > &max {
-                max = value.clone();
-                index = i;
-            }
-        }
-        (index, 2i32.pow(max as u32))
-    }
-    
-    pub fn get_empty_tiles(&self) -> Vec<u32> {
-        let mut empty_tiles: Vec<u32> = Vec::new();
-        for (index, &value) in self.data.iter().enumerate() {
-            if value == 0 {
-                empty_tiles.push(index as u32);
-            }
-        }
-        empty_tiles
-    }
-    
-    pub fn new_random_tile(&mut self) {
-        let empty_tiles = self.get_empty_tiles();
-        self.new_tile(
-            *empty_tiles.choose(&mut rand::thread_rng()).unwrap() as usize,
-            {
-                let mut rng = rand::thread_rng();
-                let weights = [90, 10];
-                let dist = WeightedIndex::new(&weights).unwrap();
-                [1, 2][dist.sample(&mut rng)]
-            },
-        );
-    }
-
-    pub fn get_tiles_snake_sum(&self) -> f64 {
-
-        let mut sum = 0.0;
-        if N == 4 {
-            sum = self.get_tiles_snake_sum_4x4();
-        }
-
-        if N == 2 {
-            sum = self.get_tiles_snake_sum_2x2();
-        }
-        
-        if N == 3 {
-            sum = self.get_tiles_snake_sum_3x3();
-        }
-
-        if N == 5 {
-            sum = self.get_tiles_snake_sum_5x5();
-        }
-
-        sum
-    }
 
 
     pub fn get_tiles_snake_sum_2x2(&self) -> f64 {
@@ -235,9 +171,9 @@ impl <const N: usize> Game<N> {
     
         let mut sum: f64 = 0.0;
     
-        for i in 0..2 {
-            for j in 0..2 {
-                sum += self.get_tile(i, j) as f64 * weight_matrix[i][j];
+        for (i, weights) in weight_matrix.iter().enumerate() {
+            for (j, weight) in weights.iter().enumerate() {
+                sum += self.get_tile(i, j) as f64 * weight;
             }
         }
     
@@ -253,9 +189,9 @@ impl <const N: usize> Game<N> {
     
         let mut sum: f64 = 0.0;
     
-        for i in 0..3 {
-            for j in 0..3 {
-                sum += self.get_tile(i, j) as f64 * weight_matrix[i][j];
+        for (i, weights) in weight_matrix.iter().enumerate() {
+            for (j, weight) in weights.iter().enumerate() {
+                sum += self.get_tile(i, j) as f64 * weight;
             }
         }
     
@@ -274,9 +210,9 @@ impl <const N: usize> Game<N> {
     
         let mut sum: f64 = 0.0;
     
-        for i in 0..4 {
-            for j in 0..4 {
-                sum += self.get_tile(i, j) as f64 * weight_matrix[i][j];
+        for (i, weights) in weight_matrix.iter().enumerate() {
+            for (j, weight) in weights.iter().enumerate() {
+                sum += self.get_tile(i, j) as f64 * weight;
             }
         }
     
@@ -284,7 +220,6 @@ impl <const N: usize> Game<N> {
     }
 
 
-
     pub fn get_tiles_snake_sum_5x5(&self) -> f64 {
         let weight_matrix = [
             [1048576.0, 524288.0, 262144.0, 131072.0, 65536.0], // Highest priority row
@@ -296,9 +231,9 @@ impl <const N: usize> Game<N> {
     
         let mut sum: f64 = 0.0;
     
-        for i in 0..5 {
-            for j in 0..5 {
-                sum += self.get_tile(i, j) as f64 * weight_matrix[i][j];
+        for (i, weights) in weight_matrix.iter().enumerate() {
+            for (j, weight) in weights.iter().enumerate() {
+                sum += self.get_tile(i, j) as f64 * weight;
             }
         }
     
@@ -310,15 +245,15 @@ impl <const N: usize> Game<N> {
     pub fn get_smoothness(&self) -> f64 {
         let mut smoothness: f64 = 0.0;
     
-        for i in 0..N {
-            for j in 0..N {
+        for i in 0..R {
+            for j in 0..C {
                 let tile_value = self.get_tile(i, j) as f64;
                 if tile_value == 0.0 {
                     continue; // Skip empty tiles
                 }
     
                 // Check right neighbor
-                if j + 1 < N {
+                if j + 1 < C {
                     let right_value = self.get_tile(i, j + 1) as f64;
                     if right_value > 0.0 {
                         smoothness -= (tile_value - right_value).abs();
@@ -326,7 +261,7 @@ impl <const N: usize> Game<N> {
                 }
     
                 // Check bottom neighbor
-                if i + 1 < N {
+                if i + 1 < R {
                     let bottom_value = self.get_tile(i + 1, j) as f64;
                     if bottom_value > 0.0 {
                         smoothness -= (tile_value - bottom_value).abs();
@@ -341,20 +276,20 @@ impl <const N: usize> Game<N> {
     pub fn get_merging_potential(&self) -> f64 {
         let mut merges: f64 = 0.0;
     
-        for i in 0..N {
-            for j in 0..N {
+        for i in 0..R {
+            for j in 0..C {
                 let tile_value = self.get_tile(i, j) as f64;
                 if tile_value == 0.0 {
                     continue; // Skip empty tiles
                 }
     
                 // Check right neighbor
-                if j + 1 < N && self.get_tile(i, j) == self.get_tile(i, j + 1) {
+                if j + 1 < C && self.get_tile(i, j) == self.get_tile(i, j + 1) {
                     merges += tile_value;
                 }
     
                 // Check bottom neighbor
-                if i + 1 < N && self.get_tile(i, j) == self.get_tile(i + 1, j) {
+                if i + 1 < R && self.get_tile(i, j) == self.get_tile(i + 1, j) {
                     merges += tile_value;
                 }
             }
@@ -367,36 +302,15 @@ impl <const N: usize> Game<N> {
     pub fn get_monotonicaly(&self) -> f64 {
         let mut value = 0.0;
 
-        for i in 0..(N*N - 1) {
-            let diff = (self.data[i] - self.data[i+1]).abs() as f64;
+        for i in 0..(R*C - 1) {
+            let diff = (self.data()[i] as i32 - self.data()[i+1] as i32).abs() as f64;
             value -= diff;
         }
         value
     }
 
-    pub fn get_tile(&self, row: usize, col: usize) -> i32 {
-        let index = N * row + col;
-        let value = self.data[index].clone() as u32;
-        if value == 0 {
-            return 0;
-        }
-        2i32.pow(value)
-    }
-
-    pub fn print_board(&self, txt_writer: &mut BufWriter<File>) -> Result<()> {
-        for i in 0..N {
-            for j in 0..N {
-                if self.data[i * N + j] == 0 {
-                    write!(txt_writer, "|{:4}|", 0)?;
-                }
-                else {
-                    write!(txt_writer, "|{:4}|", 2i32.pow(self.data[i * N + j] as u32))?;
-                }
-                
-            }
-            writeln!(txt_writer)?; // Newline after each row
-        }
-        writeln!(txt_writer)?; // Extra newline for separation
-        Ok(())
+    pub fn get_tile(&self, row: usize, col: usize) -> u64 {
+        let index = C * row + col;
+        self.rules.merge.value(self.data()[index] as i32)
     }
 }
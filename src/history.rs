@@ -0,0 +1,148 @@
+use crate::game::GameStatus;
+use rand_xoshiro::Xoshiro256Plus;
+use std::collections::VecDeque;
+
+/// A game as it was before one of its turns, everything needed to go back to it.
+/// The random generator is part of it, so playing the same move again spawns the same tiles.
+#[derive(Clone)]
+pub struct Snapshot {
+    // The tiles are small exponents, one byte each is plenty
+    pub(crate) data: Vec<u8>,
+    pub(crate) score: u64,
+    pub(crate) exponent_score: i32,
+    pub(crate) status: GameStatus,
+    pub(crate) already_won: bool,
+    pub(crate) rng: Xoshiro256Plus,
+}
+
+/// One turn of a game's history.
+#[derive(Clone)]
+pub struct Turn<D> {
+    pub direction: D,
+    // Score gained by the move
+    pub score: u64,
+    // The tiles spawned after the move, as (position, tile)
+    pub spawned: Vec<(usize, i32)>,
+    pub(crate) before: Snapshot,
+}
+
+/// The turns played so far, to undo them, and the undone ones, to redo them.
+/// Only the last `limit` turns are kept, so long AI games stay small.
+#[derive(Clone)]
+pub struct History<D> {
+    limit: usize,
+    turns: VecDeque<Turn<D>>,
+    // The undone moves, the next one to redo last
+    undone: Vec<D>,
+}
+
+impl<D: Copy> History<D> {
+    pub fn new(limit: usize) -> Self {
+        History {
+            limit,
+            turns: VecDeque::new(),
+            undone: Vec::new(),
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// The turns that can be undone, oldest first.
+    pub fn turns(&self) -> impl Iterator<Item = &Turn<D>> {
+        self.turns.iter()
+    }
+
+    /// The moves that can be redone, the next one first.
+    pub fn undone(&self) -> impl Iterator<Item = D> + '_ {
+        self.undone.iter().rev().copied()
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.turns.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.undone.is_empty()
+    }
+
+    pub(crate) fn record(&mut self, turn: Turn<D>) {
+        if self.limit == 0 {
+            return;
+        }
+        if self.turns.len() == self.limit {
+            self.turns.pop_front();
+        }
+        self.turns.push_back(turn);
+    }
+
+    // A new move makes the undone ones unreachable
+    pub(crate) fn forget_undone(&mut self) {
+        self.undone.clear();
+    }
+
+    pub(crate) fn undo(&mut self) -> Option<Snapshot> {
+        let turn = self.turns.pop_back()?;
+        self.undone.push(turn.direction);
+        Some(turn.before)
+    }
+
+    pub(crate) fn redo(&mut self) -> Option<D> {
+        self.undone.pop()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::{Direction, Game};
+    use crate::state::GameState;
+    use strum::IntoEnumIterator;
+
+    // Plays `turns` moves, each the first legal one, and returns the position before every turn and after the last
+    fn play(game: &mut Game<4>, turns: usize) -> Vec<String> {
+        let mut positions = vec![game.notation().to_string()];
+        for _ in 0..turns {
+            let direction = Direction::iter().find(|&direction| game.legal_moves().contains(direction)).unwrap();
+            assert!(game.play_turn(direction).moved);
+            positions.push(game.notation().to_string());
+        }
+        positions
+    }
+
+    fn spawns(game: &Game<4>) -> Vec<Vec<(usize, i32)>> {
+        game.history().unwrap().turns().map(|turn| turn.spawned.clone()).collect()
+    }
+
+    #[test]
+    fn redone_turns_spawn_the_same_tiles() {
+        let mut game = Game::<4>::with_seed(11).with_history(100);
+        let positions = play(&mut game, 20);
+        let spawned = spawns(&game);
+        for k in 1..=8 {
+            assert!(game.undo());
+            assert_eq!(game.notation().to_string(), positions[20 - k]);
+        }
+        assert_eq!(game.history().unwrap().undone().count(), 8);
+        for k in (1..=8).rev() {
+            assert!(game.redo());
+            assert_eq!(game.notation().to_string(), positions[21 - k]);
+        }
+        assert!(!game.redo());
+        assert_eq!(spawns(&game), spawned);
+    }
+
+    #[test]
+    fn the_oldest_turn_goes_past_the_limit() {
+        let mut game = Game::<4>::with_seed(11).with_history(3);
+        let positions = play(&mut game, 5);
+        let history = game.history().unwrap();
+        assert_eq!(history.turns().count(), 3);
+        for _ in 0..3 {
+            assert!(game.undo());
+        }
+        // The first two turns were dropped
+        assert!(!game.undo());
+        assert_eq!(game.notation().to_string(), positions[2]);
+    }
+}
@@ -0,0 +1,183 @@
+use crate::board::Board;
+use crate::display::{self, CubeView};
+use crate::engine::{Engine, Geometry};
+use crate::game::{Direction, Game, GameStatus};
+use crate::state::GameState;
+use crate::three_d_game::{ThreeDDirection, ThreeDGame};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::QueueableCommand;
+use std::io::{Result, Write};
+
+// What a single key press means to the player, regardless of the game status.
+enum Action<D> {
+    Move(D),
+    Quit,
+    Yes,
+    No,
+    Restart,
+    Undo,
+    Redo,
+    // Views of the cube, see `CubeView`
+    Rotate,
+    Toggle(usize),
+    Ignore,
+}
+
+// The keys of the prompts, undo and redo, the same in every mode
+fn action<D>(key: KeyEvent) -> Action<D> {
+    if key.modifiers.contains(KeyModifiers::CONTROL) {
+        match key.code {
+            KeyCode::Char('c') => return Action::Quit,
+            KeyCode::Char('r') => return Action::Redo,
+            _ => {}
+        }
+    }
+    match key.code {
+        KeyCode::Char('q') | KeyCode::Char('Q') | KeyCode::Esc => Action::Quit,
+        KeyCode::Char('y') | KeyCode::Char('Y') => Action::Yes,
+        KeyCode::Char('n') | KeyCode::Char('N') => Action::No,
+        KeyCode::Char('r') | KeyCode::Char('R') => Action::Restart,
+        KeyCode::Char('u') | KeyCode::Char('U') => Action::Undo,
+        _ => Action::Ignore,
+    }
+}
+
+fn action_2d(key: KeyEvent) -> Action<Direction> {
+    match key.code {
+        KeyCode::Up | KeyCode::Char('w') | KeyCode::Char('W') => Action::Move(Direction::Up),
+        KeyCode::Down | KeyCode::Char('s') | KeyCode::Char('S') => Action::Move(Direction::Down),
+        KeyCode::Left | KeyCode::Char('a') | KeyCode::Char('A') => Action::Move(Direction::Left),
+        KeyCode::Right | KeyCode::Char('d') | KeyCode::Char('D') => Action::Move(Direction::Right),
+        _ => action(key),
+    }
+}
+
+// The key map of `2048-3d/js/keyboard_input_manager.js`: the arrows, WASD and the vim keys move within the layers,
+// Q and R inward, E and F outward, and the digits hide or show a layer. Since Q and R are moves,
+// they only quit and restart when no move can be played, Esc quits any time.
+fn action_3d(key: KeyEvent, status: &GameStatus, layers: usize) -> Action<ThreeDDirection> {
+    if key.modifiers.contains(KeyModifiers::CONTROL) {
+        return action(key);
+    }
+    let playing = matches!(status, GameStatus::Ongoing | GameStatus::Won);
+    match key.code {
+        KeyCode::Char(digit @ '1'..='9') if (digit as usize - '1' as usize) < layers => {
+            Action::Toggle(digit as usize - '1' as usize)
+        }
+        KeyCode::Char('v') | KeyCode::Char('V') => Action::Rotate,
+        code if playing => match code {
+            KeyCode::Up | KeyCode::Char('w' | 'W' | 'k' | 'K') => Action::Move(ThreeDDirection::Up),
+            KeyCode::Down | KeyCode::Char('s' | 'S' | 'j' | 'J') => Action::Move(ThreeDDirection::Down),
+            KeyCode::Left | KeyCode::Char('a' | 'A' | 'h' | 'H') => Action::Move(ThreeDDirection::Left),
+            KeyCode::Right | KeyCode::Char('d' | 'D' | 'l' | 'L') => Action::Move(ThreeDDirection::Right),
+            KeyCode::Char('q' | 'Q' | 'r' | 'R') => Action::Move(ThreeDDirection::Inward),
+            KeyCode::Char('e' | 'E' | 'f' | 'F') => Action::Move(ThreeDDirection::Outward),
+            _ => action(key),
+        },
+        _ => action(key),
+    }
+}
+
+// A new game of the same variant, keeping as many turns to undo
+fn restart<G: Geometry>(game: &Engine<G>) -> Engine<G> {
+    let restarted = Engine::with_rules(rand::random(), game.rules().clone());
+    match game.history() {
+        Some(history) => restarted.with_history(history.limit()),
+        None => restarted,
+    }
+}
+
+/// Runs an interactive game in the terminal, starting from `game`, until the player quits.
+/// Moves can be undone (u) and redone (Ctrl-R) when the game keeps a history, see `Game::with_history`.
+/// Expects the terminal to already be in raw mode.
+pub fn play<W, const R: usize, const C: usize>(out: &mut W, board: &Board, game: Game<R, C>) -> Result<Game<R, C>>
+where
+    W: Write,
+{
+    run(out, game, |key, _| action_2d(key), |out, game, _, _| {
+        display::display_game(out, board, game).map(drop)
+    })
+}
+
+/// `play` on a cube, with the keys of the 2048-3d web version: the arrows, WASD or hjkl within the layers,
+/// Q or R inward and E or F outward, 1 to 9 to hide or show that layer and v to rotate the view.
+/// Esc quits, the last move is marked on the board.
+pub fn play_3d<W, const N: usize>(out: &mut W, board: &Board, game: ThreeDGame<N>) -> Result<ThreeDGame<N>>
+where
+    W: Write,
+{
+    run(out, game, |key, status| action_3d(key, status, N), |out, game, view, last_move| {
+        display::display_cube(out, board, game, view, last_move).map(drop)
+    })
+}
+
+// The game loop of every mode: `action` reads the keys, `draw` shows the game with the move that led to it
+fn run<W, G, A, D>(out: &mut W, mut game: Engine<G>, mut action: A, mut draw: D) -> Result<Engine<G>>
+where
+    W: Write,
+    G: Geometry,
+    A: FnMut(KeyEvent, &GameStatus) -> Action<G::Move>,
+    D: FnMut(&mut W, &Engine<G>, &CubeView, Option<G::Move>) -> Result<()>,
+{
+    let mut view = CubeView::default();
+    // Kept apart from the history, which may keep no turns at all
+    let mut last_move = None;
+    draw(out, &game, &view, last_move)?;
+    out.flush()?;
+
+    loop {
+        let key = match event::read()? {
+            Event::Key(key) => key,
+            Event::Resize(_, _) => {
+                draw(out, &game, &view, last_move)?;
+                out.flush()?;
+                continue;
+            }
+            _ => continue,
+        };
+        match (game.status(), action(key, game.status())) {
+            // `play_turn` ignores the moves that don't change the board
+            (GameStatus::Ongoing, Action::Move(direction)) => {
+                if game.play_turn(direction).moved {
+                    last_move = Some(direction);
+                }
+            }
+            (GameStatus::Ongoing, Action::Quit) => game.interrupt(),
+            (GameStatus::Interrupted, Action::Yes) | (GameStatus::Interrupted, Action::Quit) => break,
+            (GameStatus::Interrupted, Action::No) => game.go_on(),
+            // "quit?" after reaching 2048: anything but yes keeps playing on the same board.
+            (GameStatus::Won, Action::Yes) | (GameStatus::Won, Action::Quit) => break,
+            (GameStatus::Won, Action::No) => game.go_on(),
+            (GameStatus::Won, Action::Move(direction)) => {
+                if game.play_turn(direction).moved {
+                    last_move = Some(direction);
+                }
+            }
+            (GameStatus::Lost, Action::Quit) => break,
+            (GameStatus::Lost, Action::Restart) => {
+                game = restart(&game);
+                last_move = None;
+            }
+            // Undoing leads to the position after the turn before, redoing replays the next undone move
+            (GameStatus::Ongoing | GameStatus::Won | GameStatus::Lost, Action::Undo) => {
+                if game.undo() {
+                    last_move = game.history().and_then(|history| history.turns().last()).map(|turn| turn.direction);
+                }
+            }
+            (GameStatus::Ongoing | GameStatus::Won, Action::Redo) => {
+                let next = game.history().and_then(|history| history.undone().next());
+                if game.redo() {
+                    last_move = next;
+                }
+            }
+            (_, Action::Rotate) => view.rotate(),
+            (_, Action::Toggle(slice)) => view.toggle(slice),
+            _ => continue,
+        }
+        draw(out, &game, &view, last_move)?;
+        out.flush()?;
+    }
+
+    out.queue(crossterm::cursor::Show)?.flush()?;
+    Ok(game)
+}
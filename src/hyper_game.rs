@@ -0,0 +1,77 @@
+use strum::IntoEnumIterator;
+
+use crate::{engine::{AxisMove, Engine, Geometry}, moves::Move, rules::Rules, state::GameState};
+
+/// A move of a `D` dimensional board, sliding its lines along `axis` towards their first or last cell.
+/// The moves of a board are its axes in order, each towards the start then towards the end.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct HyperDirection<const D: usize> {
+    pub axis: usize,
+    pub towards_start: bool,
+}
+
+impl<const D: usize> HyperDirection<D> {
+    fn from_index(index: usize) -> Self {
+        HyperDirection {
+            axis: index / 2,
+            towards_start: index.is_multiple_of(2),
+        }
+    }
+}
+
+impl<const D: usize> IntoEnumIterator for HyperDirection<D> {
+    type Iterator = std::iter::Map<std::ops::Range<usize>, fn(usize) -> Self>;
+
+    fn iter() -> Self::Iterator {
+        (0..2 * D).map(Self::from_index)
+    }
+}
+
+impl<const D: usize> Move for HyperDirection<D> {
+    fn index(self) -> usize {
+        2 * self.axis + usize::from(!self.towards_start)
+    }
+}
+
+/// The game on a hypercube of `D` dimensions and side `N`, e.g. `HyperGame<4, 3>` for a 3x3x3x3 board.
+pub type HyperGame<const D: usize, const N: usize> = Engine<Hypercube<D, N>>;
+
+/// The geometry of `HyperGame`, from 2 to 4 dimensions so that its moves fit in a `MoveSet`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Hypercube<const D: usize, const N: usize>;
+
+impl<const D: usize, const N: usize> Geometry for Hypercube<D, N> {
+    type Move = HyperDirection<D>;
+    // Up to 8^4 cells, too many to copy with every state
+    type Cells = Box<[u8]>;
+
+    const SHAPE: &'static [usize] = {
+        assert!(D >= 2 && D <= 4, "a hypercube has 2 to 4 dimensions");
+        &[N; D]
+    };
+
+    fn axis_move(direction: HyperDirection<D>) -> AxisMove {
+        AxisMove {
+            axis: direction.axis,
+            towards_start: direction.towards_start,
+        }
+    }
+}
+
+impl<const D: usize, const N: usize> Default for HyperGame<D, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const D: usize, const N: usize> HyperGame<D, N> {
+    /// A new game with a random seed, see `seed()` to replay it.
+    pub fn new() -> Self {
+        Self::with_seed(rand::random())
+    }
+    /// A new game where the initial board and every spawned tile are determined by `seed`.
+    pub fn with_seed(seed: u64) -> Self {
+        Self::with_rules(seed, Rules::default())
+    }
+}
+
@@ -0,0 +1,21 @@
+pub mod algorithm;
+pub mod bitboard;
+pub mod board;
+pub mod bonus;
+pub mod display;
+pub mod engine;
+pub mod experiment;
+pub mod game;
+pub mod history;
+pub mod human;
+pub mod hyper_game;
+pub mod merge;
+pub mod moves;
+pub mod notation;
+pub mod rules;
+pub mod spawn;
+pub mod state;
+pub mod strategies;
+pub mod symmetry;
+pub mod three_d_game;
+pub mod zobrist;
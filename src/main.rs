@@ -1,118 +1,218 @@
 #![cfg_attr(feature = "clippy", feature(plugin))]
 #![cfg_attr(feature = "clippy", plugin(clippy))]
-use std::collections::HashMap;
-use std::process::exit;
-use std::time::SystemTime;
-mod algorithm;
-mod board;
-mod display;
-mod game;
-mod three_d_game;
-mod strategies;
-use crate::strategies::expectimax::ExpectimaxStrategy;
-use crate::strategies::expectimax_three_d::ExpectimaxStrategyThreeD;
-use core::time;
-use std::io::{stdout, BufWriter, Write};
-use std::thread;
-use std::time::Duration;
-use game::{Direction, Game};
-use strategies::strategy::Strategy;
-use three_d_game::ThreeDDirection;
-use std::fs::{File, OpenOptions};
-use chrono::prelude::*;
-use rand::Rng; // 0.8.5
-
-
-const BOARD_DIMENSION: usize = 4;
-const DEPTH: usize = 2;
-const GAMES_TO_RUN: usize = 1;
+#[macro_use]
+mod cli;
+
+use clap::Parser;
+use cli::{AiArgs, AnalyzeArgs, Cli, Command, MigrateArgs, PlayArgs, StrategyName};
+use game_2048::experiment::{self, GameRecord, Summary};
+use game_2048::strategies::expectimax::{ExpectimaxStrategy, Utility};
+use game_2048::strategies::transposition::TranspositionTable;
+use game_2048::game::Game;
+use game_2048::three_d_game::ThreeDGame;
+use game_2048::hyper_game::HyperGame;
+use game_2048::notation::Notation;
+use game_2048::rules::Rules;
+use game_2048::state::GameState;
+use game_2048::{board, display, human};
+use std::fs::File;
+use std::io::{stdout, BufReader, BufWriter, StdoutLock, Write};
+use std::time::Instant;
 
 
 fn main() -> Result<(), std::io::Error>{
+    let cli = Cli::parse();
+    match cli.command.unwrap_or_else(|| Command::Ai(AiArgs::default())) {
+        Command::Play(args) => with_raw_mode(|| play_on_board(&args)),
+        Command::Ai(args) => with_raw_mode(|| run_ai_on_board(&args, true)),
+        Command::Bench(args) => run_ai_on_board(&args, false),
+        Command::Analyze(args) => analyze(&args),
+        Command::Migrate(args) => migrate(&args),
+    }
+}
+
+fn with_raw_mode<F>(f: F) -> Result<(), std::io::Error>
+where
+    F: FnOnce() -> Result<(), std::io::Error>,
+{
+    crossterm::terminal::enable_raw_mode()?;
+    let result = f();
+    crossterm::terminal::disable_raw_mode()?;
+    result
+}
+
+// The game a run starts from: the `--position` if given, otherwise a new board
+fn new_game<G: GameState>(position: Option<&Notation>, seed: u64, rules: Rules) -> Result<G, std::io::Error> {
+    match position {
+        Some(position) => G::from_notation(position, seed, rules)
+            .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidInput, error)),
+        None => Ok(G::with_rules(seed, rules)),
+    }
+}
+
+// People play the 2D boards and the cubes
+fn play_on_board(args: &PlayArgs) -> Result<(), std::io::Error> {
+    let size = args.board_size();
+    match args.dimensions() {
+        3 if !size.is_square() => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "3D boards are cubes, their --size is a single side length",
+        )),
+        3 => with_board_size!(size.rows, play_3d(args)),
+        2 => with_board_shape!(size, play(args)),
+        _ => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "only the AI plays 4D boards, see `ai --dimensions 4`",
+        )),
+    }
+}
+
+fn play<const R: usize, const C: usize>(args: &PlayArgs) -> Result<(), std::io::Error> {
+    let stdout_raw = stdout();
+    let mut stdout = BufWriter::new(stdout_raw.lock());
+    let board = board::Board::new();
+    let game = new_game::<Game<R, C>>(args.position.as_ref(), args.seed.unwrap_or_else(rand::random), args.rules.rules()?)?
+        .with_history(args.undo_limit);
+    human::play(&mut stdout, &board, game)?;
+    Ok(())
+}
+
+fn play_3d<const N: usize>(args: &PlayArgs) -> Result<(), std::io::Error> {
     let stdout_raw = stdout();
     let mut stdout = BufWriter::new(stdout_raw.lock());
-    crossterm::terminal::enable_raw_mode()?;
     let board = board::Board::new();
-    let game: Game<BOARD_DIMENSION> = game::Game::new();
-    display::display_game(&mut stdout, &board, &game)?.flush()?;
+    let game = new_game::<ThreeDGame<N>>(args.position.as_ref(), args.seed.unwrap_or_else(rand::random), args.rules.rules()?)?
+        .with_history(args.undo_limit);
+    human::play_3d(&mut stdout, &board, game)?;
+    Ok(())
+}
+
+fn new_strategy<G: GameState>(args: &AiArgs) -> ExpectimaxStrategy<G> {
+    let weights = &args.weights;
+    let mut strategy = ExpectimaxStrategy::<G>::new(args.depth, weights.alpha, weights.beta, weights.gamma, weights.delta, weights.lambda);
+    strategy.utility = match args.strategy {
+        StrategyName::Expectimax => Utility::Merged,
+        StrategyName::ExpectimaxWeighted => Utility::Weighted,
+    };
+    strategy.samples = args.samples;
+    strategy.min_probability = args.min_probability;
+    strategy.cache = TranspositionTable::new(args.cache_mb << 20, G::SHAPE.iter().product());
+    strategy
+}
+
+// The size and dimensions of the board are only known at runtime
+fn run_ai_on_board(args: &AiArgs, display: bool) -> Result<(), std::io::Error> {
+    let size = args.board_size();
+    match args.dimensions() {
+        3 | 4 if !size.is_square() => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "3D and 4D boards are hypercubes, their --size is a single side length",
+        )),
+        3 => with_board_size!(size.rows, run_ai_3d(args, display)),
+        4 => with_board_size!(size.rows, run_ai_4d(args, display)),
+        _ => with_board_shape!(size, run_ai_2d(args, display)),
+    }
+}
+
+fn run_ai_2d<const R: usize, const C: usize>(args: &AiArgs, display: bool) -> Result<(), std::io::Error> {
+    run_ai::<Game<R, C>, _>(args, display, |out, board, game, _| display::display_game(out, board, game).map(drop))
+}
 
+fn run_ai_3d<const N: usize>(args: &AiArgs, display: bool) -> Result<(), std::io::Error> {
+    let view = display::CubeView::default();
+    run_ai::<ThreeDGame<N>, _>(args, display, |out, board, game, last_move| {
+        display::display_cube(out, board, game, &view, last_move).map(drop)
+    })
+}
+
+fn run_ai_4d<const N: usize>(args: &AiArgs, display: bool) -> Result<(), std::io::Error> {
+    run_ai::<HyperGame<4, N>, _>(args, display, |out, board, game, _| display::display_game(out, board, game).map(drop))
+}
+
+// Plays `args.games` games with the AI, showing the board live with `draw` when `display` is set
+// and otherwise printing one timing line per game (the `bench` subcommand).
+fn run_ai<G, D>(args: &AiArgs, display: bool, mut draw: D) -> Result<(), std::io::Error>
+where
+    G: GameState,
+    D: FnMut(&mut BufWriter<StdoutLock<'static>>, &board::Board, &G, Option<G::Move>) -> Result<(), std::io::Error>,
+{
+    let stdout_raw = stdout();
+    let mut stdout = BufWriter::new(stdout_raw.lock());
+    let board = board::Board::new();
 
     //Create log files:
-    let csv_file = File::create("results.csv")?; // Here we save the results of the game
-    let txt_file = File::create("boards.txt")?; // Here we save the board at the final stage of the game
+    let csv_file = File::create(&args.csv)?; // Here we save the results of the game
+    let txt_file = File::create(&args.boards)?; // Here we save the board at the final stage of the game
     let mut csv_writer = BufWriter::new(csv_file);
     let mut txt_writer = BufWriter::new(txt_file);
-    // Write the CSV header
-    writeln!(csv_writer, "Game Iterations,Max Tile,Score,2,4,8,16,32,64,128,256,512,1024,2048,4096,8192, 16384, 32768,65536,131072")?;
-
-
-    for i in 0..GAMES_TO_RUN {
-        let mut strategy = ExpectimaxStrategy::<BOARD_DIMENSION>::new(DEPTH, 0.0, 0.0,0.0,0.0,0.0);
-        let mut game: Game<BOARD_DIMENSION> = game::Game::new();
-        let mut iterations = 0;
-        let mut first_occurrence: HashMap<i32, usize> = HashMap::new();
+    GameRecord::write_csv_header(&mut csv_writer)?;
 
-        loop {
-            if game.check_if_lost() {
-                break;
+    let mut summary = Summary::default();
+    let started = Instant::now();
+    for i in 0..args.games {
+        let mut strategy = new_strategy::<G>(args);
+        let game_started = Instant::now();
+        let seed = match args.seed {
+            Some(seed) => seed.wrapping_add(i as u64),
+            None => rand::random(),
+        };
+        let game = new_game::<G>(args.position.as_ref(), seed, args.rules.rules()?)?;
+        let (game, record) = experiment::play_game(game, &mut strategy, |game, last_move| {
+            if display {
+                draw(&mut stdout, &board, game, last_move)?;
+                stdout.flush()?;
             }
-            let best_move: Option<Direction> = strategy.calculate_next_move(&game);
-            match best_move {
-                Some(_) => {
-                    let best_move = best_move.unwrap();
-                    game.movement(&best_move);
-                    
-                    let (_, max_tile) = game.get_max_tile();
-                    if !first_occurrence.contains_key(&max_tile) {
-                        first_occurrence.insert(max_tile, iterations);
-                    }
-
-                    if game.get_empty_tiles().len() > 0 {
-                        game.new_random_tile();
-                    }
-                    display::display_game(&mut stdout, &board, &game)?.flush()?;
-                }
-                None => {
-                    break;
-                }
-            }
-            iterations += 1;
-        }
-
-        let (_, max_tile) = game.get_max_tile();
-        let score = game.score();
+            Ok(())
+        })?;
 
         //print game board to log file:
         writeln!(txt_writer, "Game #{}", i+1)?;
-        writeln!(txt_writer, "")?;
+        writeln!(txt_writer, "{}", game.notation())?;
+        writeln!(txt_writer)?;
         game.print_board(&mut txt_writer)?;
-        
+
         // Save the result as a CSV row
-        writeln!(csv_writer, "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
-          iterations,
-           max_tile,
-            score,
-            *first_occurrence.get(&2).unwrap_or(&0),
-            *first_occurrence.get(&4).unwrap_or(&0),
-            *first_occurrence.get(&8).unwrap_or(&0),
-            *first_occurrence.get(&16).unwrap_or(&0),
-            *first_occurrence.get(&32).unwrap_or(&0),
-            *first_occurrence.get(&64).unwrap_or(&0),
-            *first_occurrence.get(&128).unwrap_or(&0),
-            *first_occurrence.get(&256).unwrap_or(&0),
-            *first_occurrence.get(&512).unwrap_or(&0),
-            *first_occurrence.get(&1024).unwrap_or(&0),
-            *first_occurrence.get(&2048).unwrap_or(&0),
-            *first_occurrence.get(&4096).unwrap_or(&0),
-            *first_occurrence.get(&8192).unwrap_or(&0),
-            *first_occurrence.get(&16384).unwrap_or(&0),
-            *first_occurrence.get(&32768).unwrap_or(&0),
-            *first_occurrence.get(&65536).unwrap_or(&0),
-            *first_occurrence.get(&131072).unwrap_or(&0),
-        )?;
+        record.write_csv_row(&mut csv_writer)?;
+        csv_writer.flush()?;
+
+        if !display {
+            let elapsed = game_started.elapsed();
+            writeln!(
+                stdout,
+                "game {:>4}: max tile {:>6}, score {:>7}, {:>6} moves in {:>8.2?} ({:.2?}/move), cache hits {:.1}%",
+                i + 1,
+                record.max_tile,
+                record.score.unwrap_or_default(),
+                record.iterations,
+                elapsed,
+                elapsed / record.iterations.max(1) as u32,
+                100.0 * strategy.cache.stats().hit_rate(),
+            )?;
+            stdout.flush()?;
+        }
+        summary.add(&record);
+    }
 
+    if !display {
+        writeln!(stdout, "total time: {:.2?}", started.elapsed())?;
+        write!(stdout, "{}", summary)?;
     }
-    
     Ok(())
 }
 
+fn analyze(args: &AnalyzeArgs) -> Result<(), std::io::Error> {
+    let summary = Summary::from_csv(BufReader::new(File::open(&args.csv)?))?;
+    print!("{}", summary);
+    Ok(())
+}
+
+// Rewrites a results CSV in the current format, moving the score of legacy files to the exponent score column
+fn migrate(args: &MigrateArgs) -> Result<(), std::io::Error> {
+    let records = GameRecord::read_csv(BufReader::new(File::open(&args.csv)?))?;
+    let mut csv_writer = BufWriter::new(File::create(args.output.as_ref().unwrap_or(&args.csv))?);
+    GameRecord::write_csv_header(&mut csv_writer)?;
+    for record in &records {
+        record.write_csv_row(&mut csv_writer)?;
+    }
+    csv_writer.flush()
+}
@@ -0,0 +1,206 @@
+use std::fmt::Debug;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Decides which tiles merge when they slide into each other, and what the tiles are worth.
+/// Tiles are stored as small positive integers (0 being an empty cell), the rule gives them their meaning.
+pub trait MergeRule: Debug + Send + Sync {
+    /// The tile made from `a` sliding into its neighbour `b`, if they merge.
+    fn merge(&self, a: i32, b: i32) -> Option<i32>;
+
+    /// The number written on the tile, `u64::MAX` for those too large to write.
+    fn value(&self, tile: i32) -> u64;
+
+    /// Whether the only merges are equal tiles into the next one, those the tables of `Bitboard` know.
+    fn doubles(&self) -> bool {
+        false
+    }
+}
+
+/// The original game: equal tiles merge into their double, tile `n` is worth 2^n.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PowersOfTwo;
+
+impl MergeRule for PowersOfTwo {
+    fn merge(&self, a: i32, b: i32) -> Option<i32> {
+        if a == b {
+            Some(a + 1)
+        } else {
+            None
+        }
+    }
+
+    fn doubles(&self) -> bool {
+        true
+    }
+
+    fn value(&self, tile: i32) -> u64 {
+        if tile == 0 {
+            0
+        } else {
+            1u64.checked_shl(tile as u32).unwrap_or(u64::MAX)
+        }
+    }
+}
+
+/// Equal tiles merge into the next power of three, tile `n` is worth 3^n.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PowersOfThree;
+
+impl MergeRule for PowersOfThree {
+    fn merge(&self, a: i32, b: i32) -> Option<i32> {
+        PowersOfTwo.merge(a, b)
+    }
+
+    fn doubles(&self) -> bool {
+        true
+    }
+
+    fn value(&self, tile: i32) -> u64 {
+        if tile == 0 {
+            0
+        } else {
+            3u64.saturating_pow(tile as u32)
+        }
+    }
+}
+
+/// Fibonacci 2048: tile `n` is the n-th number of 1, 2, 3, 5, 8, ...
+/// Two 1s, or two consecutive numbers of the sequence, merge into their sum.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Fibonacci;
+
+impl MergeRule for Fibonacci {
+    fn merge(&self, a: i32, b: i32) -> Option<i32> {
+        if (a == 1 && b == 1) || (a - b).abs() == 1 {
+            Some(a.max(b) + 1)
+        } else {
+            None
+        }
+    }
+
+    fn value(&self, tile: i32) -> u64 {
+        let (mut previous, mut current) = (1u64, 1u64);
+        for _ in 0..tile {
+            (previous, current) = (current, previous.saturating_add(current));
+        }
+        if tile == 0 {
+            0
+        } else {
+            previous
+        }
+    }
+}
+
+/// Another rule whose merges stop once they would make a tile above `max`.
+#[derive(Clone, Debug)]
+pub struct MergeCap {
+    pub rule: Arc<dyn MergeRule>,
+    pub max: i32,
+}
+
+impl MergeCap {
+    /// Caps `rule` at its largest tile worth at most `max_value`.
+    pub fn new(rule: Arc<dyn MergeRule>, max_value: u64) -> Self {
+        let max = (1..64).take_while(|&tile| rule.value(tile) <= max_value).last().unwrap_or(0);
+        MergeCap { rule, max }
+    }
+}
+
+impl MergeRule for MergeCap {
+    fn merge(&self, a: i32, b: i32) -> Option<i32> {
+        self.rule.merge(a, b).filter(|&tile| tile <= self.max)
+    }
+
+    fn value(&self, tile: i32) -> u64 {
+        self.rule.value(tile)
+    }
+}
+
+/// The merge rules that can be picked by name: `classic`, `threes` or `fibonacci`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MergeRuleName {
+    Classic,
+    Threes,
+    Fibonacci,
+}
+
+impl MergeRuleName {
+    pub fn rule(self) -> Arc<dyn MergeRule> {
+        match self {
+            MergeRuleName::Classic => Arc::new(PowersOfTwo),
+            MergeRuleName::Threes => Arc::new(PowersOfThree),
+            MergeRuleName::Fibonacci => Arc::new(Fibonacci),
+        }
+    }
+}
+
+impl FromStr for MergeRuleName {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "classic" => Ok(MergeRuleName::Classic),
+            "threes" => Ok(MergeRuleName::Threes),
+            "fibonacci" => Ok(MergeRuleName::Fibonacci),
+            _ => Err(format!("unknown merge rule '{}', expected classic, threes or fibonacci", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn powers_of_two_double_equal_tiles() {
+        assert_eq!(PowersOfTwo.merge(3, 3), Some(4));
+        assert_eq!(PowersOfTwo.merge(3, 4), None);
+        assert_eq!((0..4).map(|tile| PowersOfTwo.value(tile)).collect::<Vec<_>>(), [0, 2, 4, 8]);
+        assert_eq!(PowersOfTwo.value(63), 1 << 63);
+        assert_eq!(PowersOfTwo.value(64), u64::MAX);
+    }
+
+    #[test]
+    fn powers_of_three_saturate_past_u64() {
+        assert_eq!(PowersOfThree.merge(2, 2), Some(3));
+        assert_eq!(PowersOfThree.merge(1, 2), None);
+        assert_eq!((0..4).map(|tile| PowersOfThree.value(tile)).collect::<Vec<_>>(), [0, 3, 9, 27]);
+        assert_eq!(PowersOfThree.value(40), 3u64.pow(40));
+        assert_eq!(PowersOfThree.value(41), u64::MAX);
+        assert_eq!(PowersOfThree.value(63), u64::MAX);
+    }
+
+    #[test]
+    fn fibonacci_merges_consecutive_numbers() {
+        assert_eq!(Fibonacci.merge(1, 1), Some(2));
+        assert_eq!(Fibonacci.merge(2, 3), Some(4));
+        assert_eq!(Fibonacci.merge(4, 3), Some(5));
+        assert_eq!(Fibonacci.merge(2, 2), None);
+        assert_eq!(Fibonacci.merge(2, 4), None);
+        assert_eq!((0..7).map(|tile| Fibonacci.value(tile)).collect::<Vec<_>>(), [0, 1, 2, 3, 5, 8, 13]);
+        assert_eq!(Fibonacci.value(63), 10_610_209_857_723);
+        assert_eq!(Fibonacci.value(200), u64::MAX);
+    }
+
+    #[test]
+    fn merge_cap_stops_at_the_largest_tile_below_the_value() {
+        let cap = MergeCap::new(Arc::new(PowersOfTwo), 2048);
+        assert_eq!(cap.max, 11);
+        assert_eq!(cap.merge(10, 10), Some(11));
+        assert_eq!(cap.merge(11, 11), None);
+        assert_eq!(cap.value(11), 2048);
+        assert_eq!(MergeCap::new(Arc::new(Fibonacci), 100).max, 10);
+        // Nothing below the smallest tile, nothing merges
+        assert_eq!(MergeCap::new(Arc::new(PowersOfThree), 2).max, 0);
+        assert_eq!(MergeCap::new(Arc::new(PowersOfThree), 2).merge(1, 1), None);
+    }
+
+    #[test]
+    fn merge_cap_takes_every_tile_below_u64_max() {
+        for rule in [MergeRuleName::Classic, MergeRuleName::Threes, MergeRuleName::Fibonacci] {
+            assert_eq!(MergeCap::new(rule.rule(), u64::MAX).max, 63, "{:?}", rule);
+        }
+        assert_eq!(MergeCap::new(Arc::new(PowersOfThree), 3u64.pow(40)).max, 40);
+    }
+}
@@ -0,0 +1,79 @@
+use std::fmt;
+use std::marker::PhantomData;
+use strum::IntoEnumIterator;
+
+/// A direction a board can be moved in, stored as one bit of a `MoveSet`.
+pub trait Move: Copy + Eq + IntoEnumIterator {
+    fn index(self) -> usize;
+}
+
+/// A set of moves, one bit per direction.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MoveSet<D> {
+    bits: u8,
+    direction: PhantomData<D>,
+}
+
+impl<D: Move> MoveSet<D> {
+    pub fn empty() -> Self {
+        MoveSet {
+            bits: 0,
+            direction: PhantomData,
+        }
+    }
+
+    pub fn from_bits(bits: u8) -> Self {
+        MoveSet {
+            bits,
+            direction: PhantomData,
+        }
+    }
+
+    pub fn bits(&self) -> u8 {
+        self.bits
+    }
+
+    pub fn insert(&mut self, direction: D) {
+        self.bits |= 1 << direction.index();
+    }
+
+    pub fn contains(&self, direction: D) -> bool {
+        self.bits & (1 << direction.index()) != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits == 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.bits.count_ones() as usize
+    }
+
+    /// The moves of the set, in the order of the direction enum.
+    pub fn iter(&self) -> impl Iterator<Item = D> {
+        let set = *self;
+        D::iter().filter(move |&direction| set.contains(direction))
+    }
+}
+
+impl<D: Move> Default for MoveSet<D> {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl<D: Move> FromIterator<D> for MoveSet<D> {
+    fn from_iter<I: IntoIterator<Item = D>>(iter: I) -> Self {
+        let mut set = Self::empty();
+        for direction in iter {
+            set.insert(direction);
+        }
+        set
+    }
+}
+
+impl<D: Move + fmt::Debug> fmt::Debug for MoveSet<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
@@ -0,0 +1,214 @@
+use crate::bonus::{self, TileType};
+use std::fmt;
+use std::str::FromStr;
+
+// What separates the cells along each axis, the last axis (the columns) first
+const SEPARATORS: [char; 4] = [',', '/', '|', ';'];
+
+/// Who plays next: the player's move, or the tiles spawned after it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Side {
+    Player,
+    Spawn,
+}
+
+/// A position written down as text, to paste it into a bug report, keep it as a fixture or start a game from it:
+///
+/// `4x4:0,1,2,0/0,0,0,0/0,0,3,0/0,0,0,0 player 24`
+///
+/// The shape (rows x columns, layers x rows x columns, or cubes x layers x rows x columns), the tiles as exponents
+/// (`1` is a 2 in the classic game, `0` an empty cell) with rows separated by `/`, layers by `|` and cubes by `;`, then who plays next
+/// (`player` or `spawn`) and the score. The last two can be left out for `player` and a score of 0.
+/// The typed tiles of the bonus rules are prefixed with their type, `b2` for a bonus 4 and `x3` for a bonused 8.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Notation {
+    // Length of every axis, the outermost first
+    pub shape: Vec<usize>,
+    // The tiles with the last axis varying fastest, the order of `Game::data`
+    pub tiles: Vec<i32>,
+    pub side: Side,
+    pub score: u64,
+}
+
+impl Notation {
+    /// Checks that the position fits a board of the given shape.
+    pub fn expect_shape(&self, shape: &[usize]) -> Result<(), String> {
+        if self.shape == shape {
+            Ok(())
+        } else {
+            Err(format!("expected a {} board, got {}", shape_name(shape), shape_name(&self.shape)))
+        }
+    }
+}
+
+fn shape_name(shape: &[usize]) -> String {
+    shape.iter().map(usize::to_string).collect::<Vec<_>>().join("x")
+}
+
+fn tile_name(tile: i32) -> String {
+    let prefix = match TileType::of(tile) {
+        TileType::Number => "",
+        TileType::Bonus => "b",
+        TileType::Bonused => "x",
+    };
+    format!("{}{}", prefix, bonus::exponent(tile))
+}
+
+fn parse_tile(text: &str) -> Result<i32, String> {
+    let text = text.trim();
+    let (kind, exponent) = match text.as_bytes().first() {
+        Some(b'b') => (TileType::Bonus, &text[1..]),
+        Some(b'x') => (TileType::Bonused, &text[1..]),
+        _ => (TileType::Number, text),
+    };
+    let exponent: i32 = exponent.parse().map_err(|_| format!("invalid tile '{}'", text))?;
+    // Tiles are exponents, above 63 their value doesn't fit in the score
+    if !(0..64).contains(&exponent) {
+        return Err(format!("tile {} out of range, tiles are exponents from 0 to 63", exponent));
+    }
+    if exponent == 0 && kind != TileType::Number {
+        return Err(format!("invalid tile '{}', an empty cell has no type", text));
+    }
+    Ok(kind.tile(exponent))
+}
+
+// Writes the tiles of an axis, recursing into the inner ones
+fn write_axis(f: &mut fmt::Formatter<'_>, shape: &[usize], tiles: &[i32]) -> fmt::Result {
+    if shape.len() == 1 {
+        let cells: Vec<String> = tiles.iter().map(|&tile| tile_name(tile)).collect();
+        return write!(f, "{}", cells.join(","));
+    }
+    let stride = tiles.len() / shape[0];
+    for (i, chunk) in tiles.chunks(stride).enumerate() {
+        if i > 0 {
+            write!(f, "{}", SEPARATORS[shape.len() - 1])?;
+        }
+        write_axis(f, &shape[1..], chunk)?;
+    }
+    Ok(())
+}
+
+fn parse_axis(text: &str, shape: &[usize], tiles: &mut Vec<i32>) -> Result<(), String> {
+    let parts: Vec<&str> = text.split(SEPARATORS[shape.len() - 1]).collect();
+    if parts.len() != shape[0] {
+        let what = ["cells", "rows", "layers", "cubes"][shape.len() - 1];
+        return Err(format!("expected {} {} in '{}', got {}", shape[0], what, text, parts.len()));
+    }
+    for part in parts {
+        if shape.len() == 1 {
+            tiles.push(parse_tile(part)?);
+        } else {
+            parse_axis(part, &shape[1..], tiles)?;
+        }
+    }
+    Ok(())
+}
+
+impl fmt::Display for Notation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:", shape_name(&self.shape))?;
+        write_axis(f, &self.shape, &self.tiles)?;
+        let side = match self.side {
+            Side::Player => "player",
+            Side::Spawn => "spawn",
+        };
+        write!(f, " {} {}", side, self.score)
+    }
+}
+
+impl FromStr for Notation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fields = s.split_whitespace();
+        let (shape, board) = fields
+            .next()
+            .and_then(|board| board.split_once(':'))
+            .ok_or_else(|| format!("expected <shape>:<tiles>, got '{}'", s))?;
+        let shape = shape
+            .split('x')
+            .map(|length| length.parse().ok().filter(|&length| length > 0))
+            .collect::<Option<Vec<usize>>>()
+            .ok_or_else(|| format!("invalid shape '{}'", shape))?;
+        if shape.len() > SEPARATORS.len() {
+            return Err(format!("boards have at most {} dimensions", SEPARATORS.len()));
+        }
+        let mut tiles = Vec::new();
+        parse_axis(board, &shape, &mut tiles)?;
+
+        let side = match fields.next() {
+            None | Some("player") => Side::Player,
+            Some("spawn") => Side::Spawn,
+            Some(side) => return Err(format!("unknown side '{}', expected player or spawn", side)),
+        };
+        let score = match fields.next() {
+            Some(score) => score.parse().map_err(|_| format!("invalid score '{}'", score))?,
+            None => 0,
+        };
+        if let Some(extra) = fields.next() {
+            return Err(format!("unexpected '{}' after the score", extra));
+        }
+        Ok(Notation { shape, tiles, side, score })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Game;
+    use crate::hyper_game::HyperGame;
+    use crate::rules::Rules;
+    use crate::state::GameState;
+    use crate::three_d_game::ThreeDGame;
+
+    // Parses the position into a game and writes the game back
+    fn round_trip<G: GameState>(position: &str) -> String {
+        let notation: Notation = position.parse().unwrap();
+        assert_eq!(notation.to_string(), position);
+        G::from_notation(&notation, 0, Rules::default()).unwrap().notation().to_string()
+    }
+
+    #[test]
+    fn every_shape_reads_back_what_it_writes() {
+        for position in ["4x4:0,1,2,0/0,0,0,0/0,0,3,0/0,0,0,11 player 24", "4x4:1,1,0,0/0,0,0,0/0,0,0,0/0,0,0,0 player 0"] {
+            assert_eq!(round_trip::<Game<4>>(position), position);
+        }
+        let rectangle = "2x3:1,0,2/0,3,0 player 8";
+        assert_eq!(round_trip::<Game<2, 3>>(rectangle), rectangle);
+        let cube = "2x2x2:1,0/0,2|0,3/4,0 player 16";
+        assert_eq!(round_trip::<ThreeDGame<2>>(cube), cube);
+        let hypercube = "2x2x2x2:1,0/0,0|0,0/0,2;0,0/3,0|0,0/0,4 player 0";
+        assert_eq!(round_trip::<HyperGame<4, 2>>(hypercube), hypercube);
+    }
+
+    #[test]
+    fn side_and_score_default_to_the_player_and_0() {
+        let notation: Notation = "2x2:1,0/0,2".parse().unwrap();
+        assert_eq!((notation.side, notation.score), (Side::Player, 0));
+        assert_eq!(notation.tiles, [1, 0, 0, 2]);
+        assert_eq!("2x2:1,0/0,2 spawn 4".parse::<Notation>().unwrap().side, Side::Spawn);
+    }
+
+    #[test]
+    fn malformed_positions_are_rejected() {
+        let error = |position: &str| position.parse::<Notation>().unwrap_err();
+        assert_eq!(error("2x2:1,0/0,2/0,0"), "expected 2 rows in '1,0/0,2/0,0', got 3");
+        assert_eq!(error("2x2:1,0/0"), "expected 2 cells in '0', got 1");
+        assert_eq!(error("2x2:1,a/0,2"), "invalid tile 'a'");
+        assert_eq!(error("2x2:1,64/0,2"), "tile 64 out of range, tiles are exponents from 0 to 63");
+        assert_eq!(error("2x2:1,-1/0,2"), "tile -1 out of range, tiles are exponents from 0 to 63");
+        assert_eq!(error("2x2:1,b0/0,2"), "invalid tile 'b0', an empty cell has no type");
+        assert_eq!(error("2x2:1,0/0,2 player 4 extra"), "unexpected 'extra' after the score");
+        assert!(error("2x0:").starts_with("invalid shape"));
+    }
+
+    #[test]
+    fn games_only_take_positions_of_their_shape() {
+        let notation: Notation = "2x3:1,0,2/0,3,0".parse().unwrap();
+        assert_eq!(Game::<3, 2>::from_notation(&notation, 0, Rules::default()).err().unwrap(), "expected a 3x2 board, got 2x3");
+        assert_eq!(ThreeDGame::<2>::from_notation(&notation, 0, Rules::default()).err().unwrap(), "expected a 2x2x2 board, got 2x3");
+        // Typed tiles need the bonus rules
+        let bonus: Notation = "2x2:b2,0/0,1".parse().unwrap();
+        assert!(Game::<2>::from_notation(&bonus, 0, Rules::default()).is_err());
+    }
+}
@@ -0,0 +1,56 @@
+use crate::bonus::{Bonus, TileType, Typed};
+use crate::merge::{MergeRule, PowersOfTwo};
+use crate::spawn::{SpawnRule, WeightedSpawn};
+use std::sync::Arc;
+
+/// Everything that differs between the 2048 variants a game can be played with.
+/// Cheap to clone, the search clones it with every state.
+#[derive(Clone, Debug)]
+pub struct Rules {
+    pub spawn: Arc<dyn SpawnRule>,
+    pub merge: Arc<dyn MergeRule>,
+    // The value of the tile that wins the game
+    pub target: u64,
+    // The bonus tiles of the 2048-3d web version, none in the classic game
+    pub bonus: Option<Arc<Bonus>>,
+}
+
+impl Rules {
+    /// The classic merges with the given spawn rule.
+    pub fn new<S: SpawnRule + 'static>(spawn: S) -> Self {
+        Rules {
+            spawn: Arc::new(spawn),
+            merge: Arc::new(PowersOfTwo),
+            target: 2048,
+            bonus: None,
+        }
+    }
+
+    pub fn with_merge(mut self, merge: Arc<dyn MergeRule>) -> Self {
+        self.merge = merge;
+        self
+    }
+
+    pub fn with_target(mut self, target: u64) -> Self {
+        self.target = target;
+        self
+    }
+
+    /// Adds the bonus tiles, typing the tiles of the merge rule set so far, see `Typed`.
+    pub fn with_bonus(mut self, bonus: Bonus) -> Self {
+        self.merge = Arc::new(Typed { rule: self.merge });
+        self.bonus = Some(Arc::new(bonus));
+        self
+    }
+
+    /// Whether making `tile` wins the game, only the tiles of the classic game do.
+    pub fn wins(&self, tile: i32) -> bool {
+        tile > 0 && TileType::of(tile) == TileType::Number && self.merge.value(tile) >= self.target
+    }
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self::new(WeightedSpawn::classic())
+    }
+}
@@ -0,0 +1,191 @@
+use crate::merge::MergeRule;
+use rand::{Rng, RngCore};
+use std::fmt::Debug;
+use std::str::FromStr;
+
+/// Decides which tiles appear on the board after every move.
+/// Both the games and the search read it, so the chance nodes of a strategy
+/// always match the real distribution of the game being played.
+pub trait SpawnRule: Debug + Send + Sync {
+    /// The exponents a new tile can have, with their probabilities (summing to 1).
+    fn outcomes(&self) -> &[(i32, f64)];
+
+    /// How many tiles appear after every move, fewer when the board has no room left.
+    fn tiles_per_turn(&self) -> usize {
+        1
+    }
+
+    /// The exponent of the tiles on the initial board: the most likely outcome.
+    fn initial_value(&self) -> i32 {
+        self.outcomes()
+            .iter()
+            .fold((0, f64::NEG_INFINITY), |best, &(value, p)| if p > best.1 { (value, p) } else { best })
+            .0
+    }
+
+    /// Draws the exponent of one new tile.
+    fn sample(&self, rng: &mut dyn RngCore) -> i32 {
+        let outcomes = self.outcomes();
+        let mut x: f64 = rng.gen();
+        for &(value, p) in outcomes {
+            if x < p {
+                return value;
+            }
+            x -= p;
+        }
+        // Only reachable through rounding, the probabilities sum to 1
+        outcomes[outcomes.len() - 1].0
+    }
+}
+
+/// A fixed distribution over tile values, the rule of every 2048 variant we play.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WeightedSpawn {
+    outcomes: Vec<(i32, f64)>,
+    tiles_per_turn: usize,
+}
+
+impl WeightedSpawn {
+    /// `weights` are (exponent, weight) pairs, the weights don't need to sum to 1.
+    pub fn new(weights: &[(i32, f64)], tiles_per_turn: usize) -> Self {
+        assert!(!weights.is_empty(), "a spawn rule needs at least one tile value");
+        let total: f64 = weights.iter().map(|(_, weight)| weight).sum();
+        WeightedSpawn {
+            outcomes: weights.iter().map(|&(value, weight)| (value, weight / total)).collect(),
+            tiles_per_turn,
+        }
+    }
+
+    /// The original game: 90% 2s and 10% 4s.
+    pub fn classic() -> Self {
+        Self::new(&[(1, 90.0), (2, 10.0)], 1)
+    }
+
+    pub fn even() -> Self {
+        Self::new(&[(1, 1.0), (2, 1.0)], 1)
+    }
+
+    pub fn fours_only() -> Self {
+        Self::new(&[(2, 1.0)], 1)
+    }
+
+    pub fn eighty_twenty() -> Self {
+        Self::new(&[(1, 80.0), (2, 20.0)], 1)
+    }
+
+    pub fn with_eights() -> Self {
+        Self::new(&[(1, 80.0), (2, 15.0), (3, 5.0)], 1)
+    }
+
+    pub fn with_tiles_per_turn(mut self, tiles_per_turn: usize) -> Self {
+        self.tiles_per_turn = tiles_per_turn;
+        self
+    }
+}
+
+impl Default for WeightedSpawn {
+    fn default() -> Self {
+        Self::classic()
+    }
+}
+
+impl SpawnRule for WeightedSpawn {
+    fn outcomes(&self) -> &[(i32, f64)] {
+        &self.outcomes
+    }
+
+    fn tiles_per_turn(&self) -> usize {
+        self.tiles_per_turn
+    }
+}
+
+/// A spawn rule as written on the command line: a preset (`classic`, `even`, `fours`, `eighty-twenty`, `eights`)
+/// or a list of tile values and weights such as `2:90,4:10`. The values are the numbers written on the tiles,
+/// so they only become exponents with the merge rule of the game, see `resolve`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SpawnSpec {
+    // The presets spawn the first tiles of the merge rule, 2 and 4 in the classic game
+    Preset(WeightedSpawn),
+    Values(Vec<(u64, f64)>),
+}
+
+impl SpawnSpec {
+    /// The spawn rule of a game merging with `merge`, an error when a value is not one of its tiles.
+    pub fn resolve(&self, merge: &dyn MergeRule) -> Result<WeightedSpawn, String> {
+        let values = match self {
+            SpawnSpec::Preset(spawn) => return Ok(spawn.clone()),
+            SpawnSpec::Values(values) => values,
+        };
+        let mut weights = Vec::new();
+        for &(value, weight) in values {
+            // Every merge rule fits 40 tiles in a u64
+            let exponent = (1..=40)
+                .find(|&exponent| merge.value(exponent) == value)
+                .ok_or_else(|| format!("{} is not a tile of the merge rule", value))?;
+            weights.push((exponent, weight));
+        }
+        Ok(WeightedSpawn::new(&weights, 1))
+    }
+}
+
+impl Default for SpawnSpec {
+    fn default() -> Self {
+        SpawnSpec::Preset(WeightedSpawn::classic())
+    }
+}
+
+impl FromStr for SpawnSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let preset = match s {
+            "classic" => Some(WeightedSpawn::classic()),
+            "even" => Some(WeightedSpawn::even()),
+            "fours" => Some(WeightedSpawn::fours_only()),
+            "eighty-twenty" => Some(WeightedSpawn::eighty_twenty()),
+            "eights" => Some(WeightedSpawn::with_eights()),
+            _ => None,
+        };
+        if let Some(preset) = preset {
+            return Ok(SpawnSpec::Preset(preset));
+        }
+        let mut values = Vec::new();
+        for pair in s.split(',') {
+            let (value, weight) = pair
+                .split_once(':')
+                .ok_or_else(|| format!("expected <tile>:<weight>, got '{}'", pair))?;
+            let value: u64 = value.trim().parse().map_err(|_| format!("invalid tile '{}'", value))?;
+            let weight: f64 = weight.trim().parse().map_err(|_| format!("invalid weight '{}'", weight))?;
+            if weight.is_nan() || weight < 0.0 {
+                return Err(format!("invalid weight '{}'", weight));
+            }
+            values.push((value, weight));
+        }
+        if values.iter().map(|(_, weight)| weight).sum::<f64>() <= 0.0 {
+            return Err("the weights sum to zero".to_string());
+        }
+        Ok(SpawnSpec::Values(values))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merge::{Fibonacci, PowersOfThree, PowersOfTwo};
+
+    fn exponents(spec: &str, merge: &dyn MergeRule) -> Result<Vec<i32>, String> {
+        let spawn = spec.parse::<SpawnSpec>()?.resolve(merge)?;
+        Ok(spawn.outcomes().iter().map(|&(exponent, _)| exponent).collect())
+    }
+
+    #[test]
+    fn values_are_tiles_of_the_merge_rule() {
+        assert_eq!(exponents("2:90,4:10", &PowersOfTwo), Ok(vec![1, 2]));
+        assert_eq!(exponents("3:90,9:10", &PowersOfThree), Ok(vec![1, 2]));
+        assert_eq!(exponents("2:90,5:10", &Fibonacci), Ok(vec![2, 4]));
+        assert!(exponents("3:90", &PowersOfTwo).is_err());
+        assert!(exponents("2:90", &PowersOfThree).is_err());
+        // The presets are the first tiles of any rule
+        assert_eq!(exponents("classic", &PowersOfThree), Ok(vec![1, 2]));
+    }
+}
@@ -0,0 +1,159 @@
+use crate::algorithm::TileMove;
+use crate::game::{GameStatus, TurnOutcome};
+use crate::moves::{Move, MoveSet};
+use crate::notation::{Notation, Side};
+use crate::rules::Rules;
+use rand::prelude::SliceRandom;
+use rand_xoshiro::Xoshiro256Plus;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::io::{Result, Write};
+
+/// A board the strategies, the display and the game loop can play on, an `Engine` such as `Game` or `ThreeDGame`.
+pub trait GameState: Clone + Eq + Hash {
+    /// The directions the board can be moved in.
+    type Move: Move + Debug;
+
+    /// Length of every axis of the board, the outermost first, as in `Notation::shape`.
+    /// The last axis is the rows as they are printed.
+    const SHAPE: &'static [usize];
+
+    /// A new game of the variant described by `rules`, where the initial board and every spawned tile are determined by `seed`.
+    fn with_rules(seed: u64, rules: Rules) -> Self;
+    /// A game at a written down position, see `Notation`.
+    /// When it is the spawn's turn, the tiles are drawn from `seed` right away.
+    /// The notation has no exponent score, it starts at 0.
+    fn from_notation(notation: &Notation, seed: u64, rules: Rules) -> std::result::Result<Self, String>;
+
+    fn seed(&self) -> u64;
+    fn rules(&self) -> &Rules;
+    /// The tiles, one byte each, the last axis varying fastest.
+    fn data(&self) -> &[u8];
+    /// The Zobrist hash of the tiles, kept up to date by the moves and the spawns rather than computed.
+    /// Games with the same tiles have the same hash, whatever their score, see `zobrist`.
+    fn zobrist(&self) -> u64;
+    /// Sum of the values of all the tiles made by merges.
+    fn score(&self) -> u64;
+    /// Sum of their exponents, what the score used to be.
+    fn exponent_score(&self) -> i32;
+    fn status(&self) -> &GameStatus;
+    /// Whether the target tile was made, even if the game went on after that.
+    fn already_won(&self) -> bool;
+    fn interrupt(&mut self);
+    fn go_on(&mut self);
+    fn lose(&mut self);
+
+    /// The moves that change the board.
+    fn legal_moves(&self) -> MoveSet<Self::Move>;
+    /// Slides the board without spawning, returns whether it changed.
+    fn movement(&mut self, direction: &Self::Move) -> bool;
+    /// `movement`, returning where every tile went: one `TileMove` per tile of the new board,
+    /// in board cells. Empty when the move changes nothing.
+    fn traced_movement(&mut self, direction: &Self::Move) -> Vec<TileMove>;
+    /// Plays a full turn: the move, then the spawn and the new status.
+    /// Illegal moves, and any move of a lost or interrupted game, leave the game untouched.
+    /// Moving after reaching the target keeps playing.
+    fn play_turn(&mut self, direction: Self::Move) -> TurnOutcome;
+
+    fn new_tile(&mut self, position: usize, value: i32);
+    /// The generator every spawn is drawn from.
+    fn rng(&mut self) -> &mut Xoshiro256Plus;
+
+    /// Sum of the tile values weighted along a snake path from the corner, the main heuristic of the search.
+    fn get_tiles_snake_sum(&self) -> f64;
+
+    fn check_if_lost(&self) -> bool {
+        self.legal_moves().is_empty()
+    }
+
+    /// How many cells are empty, without listing them as `get_empty_tiles` does.
+    fn count_empty_tiles(&self) -> usize {
+        self.data().iter().filter(|&&tile| tile == 0).count()
+    }
+
+    fn get_empty_tiles(&self) -> Vec<u32> {
+        let mut empty_tiles: Vec<u32> = Vec::new();
+        for (index, &value) in self.data().iter().enumerate() {
+            if value == 0 {
+                empty_tiles.push(index as u32);
+            }
+        }
+        empty_tiles
+    }
+
+    fn get_tiles_sum(&self) -> i32 {
+        self.data().iter().map(|&tile| tile as i32).sum()
+    }
+
+    /// The cell of the most valuable tile, and its value.
+    fn get_max_tile(&self) -> (usize, u64) {
+        let merge = &*self.rules().merge;
+        let mut max = 0;
+        let mut index = 0;
+        for (i, &tile) in self.data().iter().enumerate() {
+            let value = merge.value(tile as i32);
+            if value > max {
+                max = value;
+                index = i;
+            }
+        }
+        (index, max)
+    }
+
+    fn new_random_tile(&mut self) -> (usize, i32) {
+        let empty_tiles = self.get_empty_tiles();
+        let position = *empty_tiles.choose(self.rng()).unwrap() as usize;
+        let spawn = self.rules().spawn.clone();
+        let value = spawn.sample(self.rng());
+        self.new_tile(position, value);
+        (position, value)
+    }
+
+    /// Spawns the tiles that appear after a move, as many as the spawn rule says and the board can hold,
+    /// then the bonus tile if the rules have one, see `Bonus`.
+    fn spawn(&mut self) -> Vec<(usize, i32)> {
+        let mut spawned = Vec::new();
+        for _ in 0..self.rules().spawn.tiles_per_turn() {
+            if self.get_empty_tiles().is_empty() {
+                break;
+            }
+            spawned.push(self.new_random_tile());
+        }
+        if let Some(bonus) = self.rules().bonus.clone() {
+            let data = self.data().to_vec();
+            if let Some(tile) = bonus.sample(&data, self.rng()) {
+                let position = *self.get_empty_tiles().choose(self.rng()).unwrap() as usize;
+                self.new_tile(position, tile);
+                spawned.push((position, tile));
+            }
+        }
+        spawned
+    }
+
+    /// The position in board notation, always with the player to move.
+    fn notation(&self) -> Notation {
+        Notation {
+            shape: Self::SHAPE.to_vec(),
+            tiles: self.data().iter().map(|&tile| tile as i32).collect(),
+            side: Side::Player,
+            score: self.score(),
+        }
+    }
+
+    /// Writes the tile values row by row, the layers of a 3D board one after the other.
+    fn print_board<W: Write>(&self, out: &mut W) -> Result<()> {
+        let columns = Self::SHAPE[Self::SHAPE.len() - 1];
+        let layer = columns * Self::SHAPE[Self::SHAPE.len() - 2];
+        for (i, row) in self.data().chunks(columns).enumerate() {
+            if i > 0 && (i * columns).is_multiple_of(layer) {
+                writeln!(out)?; // Blank line between layers
+            }
+            for &tile in row {
+                write!(out, "|{:4}|", self.rules().merge.value(tile as i32))?;
+            }
+            writeln!(out)?; // Newline after each row
+        }
+        writeln!(out)?; // Extra newline for separation
+        Ok(())
+    }
+}
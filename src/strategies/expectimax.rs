@@ -1,170 +1,241 @@
 use crate::game::Game;
-use crate::game::Direction;
-use strum::IntoEnumIterator;
+use crate::state::GameState;
 use std::f64;
-use std::collections::HashMap;
 use itertools::iproduct;
 use crate::strategies::strategy::Strategy;
-use std::fs;
+use crate::strategies::transposition::{Bound, TranspositionTable};
 use std::fs::OpenOptions;
 use std::io::prelude::*;
-use rand::Rng; 
-use std::cmp;
+use rand::{seq::SliceRandom, SeedableRng};
+use rand_xoshiro::Xoshiro256Plus;
 
+/// Memory the search cache is given by default, see `TranspositionTable`.
+pub const DEFAULT_CACHE_BYTES: usize = 64 << 20;
 
 
-pub struct ExpectimaxStrategy<const N: usize>{
-    pub cache: HashMap<Game<N>, (f64, Option<Direction>)>,
+
+/// The evaluation function used at the leaves of the search.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Utility {
+    // Snake shape sum plus a bonus for every empty tile
+    Merged,
+    // Weighted sum of the heuristics, see `gamma_utility`
+    Weighted,
+}
+
+pub struct ExpectimaxStrategy<G: GameState>{
+    // The scores of the searched positions, with the depth they were searched to
+    pub cache: TranspositionTable<G::Move>,
     pub depth: usize,
     pub alpha: f64,
     pub beta: f64,
     pub gamma: f64,
     pub delta: f64,
     pub lambda: f64,
+    pub utility: Utility,
+    // Chance nodes with more empty cells than this only try that many of them, drawn at random
+    pub samples: Option<usize>,
+    // Positions less likely than this to be reached are evaluated instead of searched further
+    pub min_probability: f64,
+    // Draws the sampled cells, seeded so that runs can be replayed
+    rng: Xoshiro256Plus,
 }
-impl<const N: usize> Strategy<N> for ExpectimaxStrategy<N> {
-    fn calculate_next_move(&mut self, game: &Game<N>) -> Option<Direction> {
+impl<G: GameState> Strategy<G> for ExpectimaxStrategy<G> {
+    fn calculate_next_move(&mut self, game: &G) -> Option<G::Move> {
+        self.cache.new_move();
         let (_best_score, best_move) = self.expectimax(game, self.depth);
         best_move
     }
 }
-impl<const N: usize> ExpectimaxStrategy<N> {
+impl<G: GameState> ExpectimaxStrategy<G> {
     pub fn new(depth: usize, alpha: f64, beta: f64, gamma: f64, delta: f64, lambda: f64) -> Self {
         ExpectimaxStrategy {
-            cache: HashMap::<Game<N>, (f64, Option<Direction>)>::new(),
-            depth: depth,
-            alpha: alpha,
-            beta: beta,
-            gamma: gamma,
-            delta: delta,
-            lambda: lambda,
+            cache: TranspositionTable::new(DEFAULT_CACHE_BYTES, G::SHAPE.iter().product()),
+            depth,
+            alpha,
+            beta,
+            gamma,
+            delta,
+            lambda,
+            utility: Utility::Merged,
+            samples: None,
+            min_probability: 0.0,
+            rng: Xoshiro256Plus::seed_from_u64(0),
         }
     }
 
-    pub fn expectimax(&mut self, state: &Game<N>, depth: usize) -> (f64, Option<Direction>) {
+    pub fn expectimax(&mut self, state: &G, depth: usize) -> (f64, Option<G::Move>) {
+        let (best_score, best_move, _) = self.search(state, depth, 1.0);
+        (best_score, best_move)
+    }
 
+    // `expectimax` of a position reached with the given probability, and whether its score is exact
+    fn search(&mut self, state: &G, depth: usize, probability: f64) -> (f64, Option<G::Move>, Bound) {
         if depth == 0 {
-            return (self.merged_utilities(state), None);
+            return (self.utility(state), None, Bound::Exact);
         }
 
         if state.check_if_lost(){
-            return (self.merged_utilities(state) / (depth * 1000) as f64, None);
+            return (self.utility(state) / (depth * 1000) as f64, None, Bound::Exact);
         }
         
 
         let mut best_score: f64 = f64::NEG_INFINITY;
         let mut best_move = None;
+        let mut bound = Bound::Exact;
          
-        for step in Direction::iter() {
+        for step in state.legal_moves().iter() {
             let mut state_after_my_turn = state.clone();
-            if !state_after_my_turn.movement(&step){ // Staying in the same state is not a valid move
-                continue;
+            state_after_my_turn.movement(&step);
+            let tiles = state_after_my_turn.rules().spawn.tiles_per_turn();
+            let (expected_value, expected_bound) = self.chance(&state_after_my_turn, tiles, depth, probability);
+            bound = bound.and(expected_bound);
+            if expected_value > best_score {
+                best_score = expected_value;
+                best_move = Some(step);
             }
-            let mut expected_value: f64 = 0.0;
-            let empty_tiles_list = state_after_my_turn.get_empty_tiles();
-            let empty_list_len = empty_tiles_list.len();
-            let all_tiles_and_possibilities: Vec<_> = iproduct!(empty_tiles_list.iter(), [1,2].iter()).map(|(a, b)| (*a, *b)).collect();
-            for (empty_index, tile_value) in all_tiles_and_possibilities {
-                let mut state_after_new_tile = state_after_my_turn.clone();
-                state_after_new_tile.new_tile(empty_index as usize, tile_value);
-                // Self::printBoard(state_after_new_tile.data());
-                let mut score = 0.0;
-                match self.cache.get(&state_after_new_tile) {
-                    Some((cache_score, _)) => score = *cache_score,
-                    None => {
-                        (score, _) = self.expectimax(&state_after_new_tile, depth - 1);
-                        self.cache.insert(state_after_new_tile, (score, None));
-                    }
-                }
-                if tile_value == 1 {
-                    score *= 0.9;
-                } else {
-                    score *= 0.1;
-                }
-                expected_value += (1.0 / empty_list_len as f64) as f64 * score;
-                if expected_value > best_score {
-                    best_score = expected_value;
-                    best_move = Some(step.clone());
-                }
+        }
+        (best_score, best_move, bound)
+    }
+
+    // The expected score over every way the game can spawn `tiles` more tiles after a move,
+    // each empty cell being equally likely and the values weighted as in the game's spawn rule.
+    // `probability` is that of reaching the position, the spawned tiles making it less likely.
+    fn chance(&mut self, state: &G, tiles: usize, depth: usize, probability: f64) -> (f64, Bound) {
+        if tiles == 0 || !state.data().contains(&0) {
+            return match state.rules().bonus.as_ref().map(|bonus| bonus.outcomes(state.data())) {
+                Some(outcomes) if !outcomes.is_empty() => self.bonus_chance(state, &outcomes, depth, probability),
+                _ => self.settled(state, depth, probability),
+            };
+        }
+
+        let empty_tiles_list = state.get_empty_tiles();
+        let empty_list_len = empty_tiles_list.len();
+        let cells = self.sample_cells(empty_tiles_list);
+        let mut expected_value: f64 = 0.0;
+        let mut bound = if cells.len() < empty_list_len { Bound::Estimate } else { Bound::Exact };
+        for (empty_index, &(tile_value, tile_probability)) in iproduct!(cells.iter(), state.rules().spawn.outcomes().iter()) {
+            let mut state_after_new_tile = state.clone();
+            state_after_new_tile.new_tile(*empty_index as usize, tile_value);
+            let reached = probability * tile_probability / empty_list_len as f64;
+            let (score, score_bound) = self.chance(&state_after_new_tile, tiles - 1, depth, reached);
+            expected_value += tile_probability / cells.len() as f64 * score;
+            bound = bound.and(score_bound);
+        }
+        (expected_value, bound)
+    }
+
+    // The expected score over the bonus tile that may appear once the regular tiles spawned, see `Bonus`:
+    // each of `outcomes` on every empty cell, or no bonus tile at all.
+    fn bonus_chance(&mut self, state: &G, outcomes: &[(i32, f64)], depth: usize, probability: f64) -> (f64, Bound) {
+        let no_bonus = 1.0 - outcomes.iter().map(|(_, p)| p).sum::<f64>();
+        let (score, mut bound) = self.settled(state, depth, probability * no_bonus);
+        let mut expected_value = no_bonus * score;
+
+        let empty_tiles_list = state.get_empty_tiles();
+        let empty_list_len = empty_tiles_list.len();
+        let cells = self.sample_cells(empty_tiles_list);
+        if cells.len() < empty_list_len {
+            bound = Bound::Estimate;
+        }
+        for (empty_index, &(tile, tile_probability)) in iproduct!(cells.iter(), outcomes.iter()) {
+            let mut state_after_bonus = state.clone();
+            state_after_bonus.new_tile(*empty_index as usize, tile);
+            let reached = probability * tile_probability / empty_list_len as f64;
+            let (score, score_bound) = self.settled(&state_after_bonus, depth, reached);
+            expected_value += tile_probability / cells.len() as f64 * score;
+            bound = bound.and(score_bound);
+        }
+        (expected_value, bound)
+    }
+
+    // The score of the position once every tile spawned, searched one move less deep
+    fn settled(&mut self, state: &G, depth: usize, probability: f64) -> (f64, Bound) {
+        if probability < self.min_probability {
+            // Not cached, the same position may be reached by a more likely path
+            return (self.utility(state), Bound::Estimate);
+        }
+        // A score searched deeper is as good
+        if let Some((score, _, bound)) = self.cache.get(state.zobrist(), state.data(), depth - 1) {
+            return (score, bound);
+        }
+        let (score, best_move, bound) = self.search(state, depth - 1, probability);
+        self.cache.insert(state.zobrist(), state.data(), depth - 1, bound, score, best_move);
+        (score, bound)
+    }
+
+    // The empty cells a chance node tries: all of them, or as many as `samples` drawn at random.
+    // The average over a sample of the cells estimates the one over all of them.
+    fn sample_cells(&mut self, empty_tiles_list: Vec<u32>) -> Vec<u32> {
+        match self.samples {
+            Some(samples) if empty_tiles_list.len() > samples => {
+                empty_tiles_list.choose_multiple(&mut self.rng, samples).copied().collect()
             }
+            _ => empty_tiles_list,
         }
-        (best_score, best_move)
     }
 
 
-    pub fn merged_utilities(&self, state: &Game<N>) -> f64 {
-        let snake_sum = state.get_tiles_snake_sum();
-        let empty_tiles = state.get_empty_tiles().len() as f64;
-        let result = snake_sum + empty_tiles * 1000.0;
-        result
+    pub fn utility(&self, state: &G) -> f64 {
+        match self.utility {
+            Utility::Merged => self.merged_utilities(state),
+            Utility::Weighted => self.gamma_utility(state),
+        }
     }
 
-    pub fn utility_snake_shape(&self, state: &Game<N>) -> f64 {
-        let sum = state.get_tiles_snake_sum();
-        sum
+    pub fn merged_utilities(&self, state: &G) -> f64 {
+        let snake_sum = state.get_tiles_snake_sum();
+        let empty_tiles = state.count_empty_tiles() as f64;
+        snake_sum + empty_tiles * 1000.0
     }
 
-    pub fn utility_smoothness(&self, state: &Game<N>) -> f64 {
-        let smoothness = state.get_smoothness();
-        smoothness
+    pub fn utility_snake_shape(&self, state: &G) -> f64 {
+        state.get_tiles_snake_sum()
     }
 
-    pub fn utility_max_tile(&self, state: &Game<N>) -> f64 {
+    pub fn utility_max_tile(&self, state: &G) -> f64 {
         let (_, max_tile) = state.get_max_tile();
         max_tile.ilog(2) as f64
     }
-    pub fn utility_divided_empty_tiles(&self, state: &Game<N>) -> f64 {
-        let non_empty_tiles = (N*N - state.get_empty_tiles().len()) as f64;
-        1.0 as f64 / (non_empty_tiles as f64 * non_empty_tiles as f64) as f64
+    pub fn utility_divided_empty_tiles(&self, state: &G) -> f64 {
+        let non_empty_tiles = (state.data().len() - state.count_empty_tiles()) as f64;
+        1.0_f64 / (non_empty_tiles * non_empty_tiles)
     }
     
-    pub fn utility_num_empty_tiles(&self, state: &Game<N>) -> f64 {
-        let empty_tiles = state.get_empty_tiles().len() as f64;
-        empty_tiles
+    pub fn utility_num_empty_tiles(&self, state: &G) -> f64 {
+        state.count_empty_tiles() as f64
     }
-    pub fn utility_sum_tiles(&self, state: &Game<N>) -> f64 {
-        let tiles_sum = state.get_tiles_sum() as f64;
-        tiles_sum
+    pub fn utility_sum_tiles(&self, state: &G) -> f64 {
+        state.get_tiles_sum() as f64
     }
-    pub fn utility_max_tile_over_empty_tiles_squared(&self, state: &Game<N>) -> f64 {
+    pub fn utility_max_tile_over_empty_tiles_squared(&self, state: &G) -> f64 {
         let (_, max_tile) = state.get_max_tile();
-        let non_empty_tiles = (N*N - state.get_empty_tiles().len()) as f64;
+        let non_empty_tiles = (state.data().len() - state.count_empty_tiles()) as f64;
         max_tile as f64 / (non_empty_tiles * non_empty_tiles)
     }
 
 
-    pub fn gamma_utility(&self, state: &Game<N>) -> f64 {
-        return self.alpha * self.utility_max_tile(state) + self.beta * self.utility_divided_empty_tiles(state) + self.gamma * self.center_utility(state) + self.delta * self.utility_snake_shape(state) + self.lambda * self.corner_utility(state); // TODO this should be - 
+    pub fn gamma_utility(&self, state: &G) -> f64 {
+        self.alpha * self.utility_max_tile(state) + self.beta * self.utility_divided_empty_tiles(state) + self.gamma * self.center_utility(state) + self.delta * self.utility_snake_shape(state) + self.lambda * self.corner_utility(state) // TODO this should be - 
     }
 
-    pub fn corner_utility(&self, state: &Game<N>) -> f64 {
-        // sleep a second
-        // std::thread::sleep(std::time::Duration::from_secs(1));
-        // println!("Snake utility got this board {:?}", state.data());
-        // let stdout_raw = stdout();
-        // let mut stdout = BufWriter::new(stdout_raw.lock());
-        // let board = board::Board::new();
-        // display::display_game(&mut stdout, &board, &state).unwrap();
+    pub fn corner_utility(&self, state: &G) -> f64 {
         let mut corner_score = 0.0f64;
         for (index, &value) in state.data().iter().enumerate() {
-            corner_score += (((index / N) as usize + index % N as usize) * value as usize) as f64;
+            // Distance from the first corner, row + column on a 2D board
+            let distance: usize = coordinates::<G>(index).sum();
+            corner_score += (distance * value as usize) as f64;
         }
         corner_score / 10.0
     }
 
-    pub fn center_utility(&self, state: &Game<N>) -> f64 {
-        // sleep a second
-        // std::thread::sleep(std::time::Duration::from_secs(1));
-        // println!("Snake utility got this board {:?}", state.data());
-        // let stdout_raw = stdout();
-        // let mut stdout = BufWriter::new(stdout_raw.lock());
-        // let board = board::Board::new();
-        // display::display_game(&mut stdout, &board, &state).unwrap();
-
+    pub fn center_utility(&self, state: &G) -> f64 {
         let mut center_score = 0.0f64;
 
         for (index, &value) in state.data().iter().enumerate() {
-            if index == 5 || index == 6 || index == 9 || index == 10 {
+            // The cells away from every border, 5, 6, 9 and 10 on a 4x4 board
+            let inner = coordinates::<G>(index).zip(G::SHAPE.iter().rev()).all(|(c, &length)| c > 0 && c + 1 < length);
+            if inner {
                 center_score += 0.0 * value as f64;
             } else {
                 center_score += 1.0* value as f64;
@@ -173,10 +244,28 @@ impl<const N: usize> ExpectimaxStrategy<N> {
         center_score / 10.0
     }
 
-    pub fn monotone_utility(&self, state: &Game<N>) -> f64 {
+    pub fn log_to_file(&self, line: &str) {
+        let mut file = OpenOptions::new().append(true).open("snake_utility.txt").unwrap();
+
+         if let Err(e) = writeln!(file, "{}", line) {
+             eprintln!("Couldn't write to file: {}", e);
+        }
+
+    }
+
+}
+
+// The heuristics that only make sense on a 2D board
+impl<const R: usize, const C: usize> ExpectimaxStrategy<Game<R, C>> {
+    pub fn utility_smoothness(&self, state: &Game<R, C>) -> f64 {
+        state.get_smoothness()
+    }
+
+    // The number of rows whose tiles decrease from left to right
+    pub fn monotone_utility(&self, state: &Game<R, C>) -> f64 {
         let mut score = 0.0;
-        for i in 0..N {
-            if(state.data()[i * N] > state.data()[i * N + 1] && state.data()[i * N + 1] > state.data()[i * N + 2] && state.data()[i * N + 2] > state.data()[i * N + 3]) {
+        for row in state.data().chunks(C) {
+            if row.windows(2).all(|pair| pair[0] > pair[1]) {
                 score += 1.0;
             }
         }
@@ -184,32 +273,101 @@ impl<const N: usize> ExpectimaxStrategy<N> {
     }
 
 
-    pub fn is_next_to_each_other((x1, y1): (i32, i32), (x2, y2): (i32,i32), state: &Game<N>) -> i32 {
-        if x1 < 0 || x1 >= N as i32 || y1 < 0 || y1 >= N as i32 || x2 < 0 || x2 >= N as i32 || y2 < 0 || y2 >= N as i32 {
-            return 0;
+    pub fn is_next_to_each_other((x1, y1): (i32, i32), (x2, y2): (i32,i32), state: &Game<R, C>) -> i32 {
+        if x1 < 0 || x1 >= R as i32 || y1 < 0 || y1 >= C as i32 || x2 < 0 || x2 >= R as i32 || y2 < 0 || y2 >= C as i32 {
+            0
         } else {
-            if state.data()[(x1 * N as i32 + y1) as usize] ==  state.data()[(x2 * N as i32 + y2) as usize]{
-                return 1;
+            if state.data()[(x1 * C as i32 + y1) as usize] ==  state.data()[(x2 * C as i32 + y2) as usize]{
+                1
             } else {
-                return 0;
+                0
             }
         }
     }
+}
 
+// The position of a cell along every axis, the last axis (the columns) first
+fn coordinates<G: GameState>(index: usize) -> impl Iterator<Item = usize> {
+    G::SHAPE.iter().rev().scan(index, |rest, &length| {
+        let coordinate = *rest % length;
+        *rest /= length;
+        Some(coordinate)
+    })
+}
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hyper_game::HyperGame;
+    use crate::rules::Rules;
+    use crate::strategies::transposition::CacheStats;
 
-    pub fn log_to_file(&self, line: &str) {
-        let mut file = OpenOptions::new()
-        .write(true)
-        .append(true)
-        .open("snake_utility.txt")
-        .unwrap();
+    fn new_strategy<G: GameState>(depth: usize) -> ExpectimaxStrategy<G> {
+        let mut strategy = ExpectimaxStrategy::new(depth, 0.0, 0.0, 0.0, 0.0, 0.0);
+        strategy.cache = TranspositionTable::new(1 << 20, G::SHAPE.iter().product());
+        strategy
+    }
 
-         if let Err(e) = writeln!(file, "{}", line) {
-             eprintln!("Couldn't write to file: {}", e);
+    #[test]
+    fn boards_of_thousands_of_cells_have_a_finite_utility() {
+        let game = HyperGame::<4, 6>::with_seed(0);
+        assert_eq!(game.data().len(), 1296);
+        let mut strategy = new_strategy(1);
+        strategy.samples = Some(4);
+        assert!(strategy.utility(&game).is_finite());
+        assert!(strategy.calculate_next_move(&game).is_some());
+
+        // The largest board, full of the largest tiles
+        let mut full = HyperGame::<4, 8>::with_seed(0);
+        for cell in 0..full.data().len() {
+            full.new_tile(cell, 63);
         }
+        assert!(new_strategy::<HyperGame<4, 8>>(1).utility(&full).is_finite());
+    }
 
+    // Two tiles on a 4x4 board, 14 empty cells for the spawns
+    fn opening() -> Game<4> {
+        Game::from_notation(&"4x4:1,0,0,0/0,0,0,0/0,0,0,0/0,0,0,2".parse().unwrap(), 0, Rules::default()).unwrap()
     }
 
-}
+    #[test]
+    fn samples_limit_the_spawns_searched() {
+        let game = opening();
+        let moves = game.legal_moves().len() as u64;
+        let lookups = |strategy: &ExpectimaxStrategy<Game<4>>| strategy.cache.stats().hits + strategy.cache.stats().misses;
+        let mut full = new_strategy::<Game<4>>(1);
+        let (_, _, bound) = full.search(&game, 1, 1.0);
+        assert_eq!(bound, Bound::Exact);
+        // Every move, every empty cell and both spawned values
+        assert_eq!(lookups(&full), moves * 14 * 2);
+
+        let mut sampled = new_strategy::<Game<4>>(1);
+        sampled.samples = Some(3);
+        let (score, best_move, bound) = sampled.search(&game, 1, 1.0);
+        assert_eq!(bound, Bound::Estimate);
+        assert_eq!(lookups(&sampled), moves * 3 * 2);
+        // The same seed samples the same cells
+        let mut again = new_strategy::<Game<4>>(1);
+        again.samples = Some(3);
+        assert_eq!(again.search(&game, 1, 1.0), (score, best_move, bound));
+    }
 
+    #[test]
+    fn unlikely_spawns_are_estimated_without_search() {
+        let game = opening();
+        let mut strategy = new_strategy::<Game<4>>(2);
+        // A spawn reaches at most 0.9 / 14 of the games
+        strategy.min_probability = 0.1;
+        let (score, best_move, bound) = strategy.search(&game, 2, 1.0);
+        assert_eq!(bound, Bound::Estimate);
+        assert!(best_move.is_some() && score.is_finite());
+        // Nothing was searched below the spawns, nor cached
+        assert_eq!(strategy.cache.stats(), CacheStats::default());
+
+        // Two spawns reach at least 0.1 / 14 * 0.1 / 13 of the games
+        let mut exact = new_strategy::<Game<4>>(2);
+        exact.min_probability = 1e-6;
+        assert_eq!(exact.search(&game, 2, 1.0).2, Bound::Exact);
+        assert!(exact.cache.stats().stores > 0);
+    }
+}
@@ -1,3 +1,3 @@
 pub mod strategy;
-pub mod expectimax; 
-pub mod expectimax_three_d;
\ No newline at end of file
+pub mod expectimax;
+pub mod transposition; 
\ No newline at end of file
@@ -1,8 +1,7 @@
-use crate::game::Game;
-use crate::game::Direction;
+use crate::state::GameState;
 
 
-pub trait Strategy<const N: usize> {
+pub trait Strategy<G: GameState> {
     // Trait method to be implemented by all strategies.
-    fn calculate_next_move(&mut self, game: &Game<N>) -> Option<Direction>;
+    fn calculate_next_move(&mut self, game: &G) -> Option<G::Move>;
 }
\ No newline at end of file
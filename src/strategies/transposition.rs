@@ -0,0 +1,258 @@
+use std::fmt;
+use std::mem::size_of;
+
+/// How much a cached score can be trusted. Expectimax has no alpha-beta window, so a score is never a bound
+/// in that sense: it is the exact expectation down to its depth, or an estimate of it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Bound {
+    // Some spawns were sampled, or left unsearched below `min_probability`
+    Estimate,
+    // Every spawn was searched down to the depth
+    Exact,
+}
+
+impl Bound {
+    /// The bound of a score made of two others, exact when both are.
+    pub fn and(self, other: Bound) -> Bound {
+        self.min(other)
+    }
+}
+
+/// What a table lookup found, see `TranspositionTable::stats`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    // Not found, or only from a shallower search
+    pub misses: u64,
+    // Of the misses, the positions found from a shallower search
+    pub shallow: u64,
+    pub stores: u64,
+    // Stores that took the place of another position
+    pub evictions: u64,
+}
+
+impl CacheStats {
+    /// Share of the lookups that found a score, 0 without lookups.
+    pub fn hit_rate(&self) -> f64 {
+        self.hits as f64 / (self.hits + self.misses).max(1) as f64
+    }
+}
+
+impl fmt::Display for CacheStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} hits, {} misses ({} shallow), {:.1}% hit rate, {} stores, {} evictions",
+            self.hits,
+            self.misses,
+            self.shallow,
+            100.0 * self.hit_rate(),
+            self.stores,
+            self.evictions
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Entry<M> {
+    key: u64,
+    score: f64,
+    best_move: Option<M>,
+    // Moves searched below the position
+    depth: u8,
+    bound: Bound,
+    // The move of the game the entry was stored during, see `new_move`.
+    // Wide enough that no game wraps it around, which would make old entries current again
+    generation: u32,
+}
+
+/// The scores of the searched positions, keyed by the Zobrist hash of their tiles (`GameState::zobrist`) whatever their score.
+/// The table never grows past the memory it is given: the slots are allocated on the first store, two per hash,
+/// and every slot keeps the tiles of its position to tell apart the positions sharing a hash.
+/// When both slots of a hash are taken, the new score replaces the one left from an earlier move,
+/// otherwise the one searched least deep.
+pub struct TranspositionTable<M> {
+    entries: Vec<Option<Entry<M>>>,
+    // The tiles of the position in every slot, `cells` per slot
+    tiles: Vec<u8>,
+    cells: usize,
+    slots: usize,
+    generation: u32,
+    stats: CacheStats,
+}
+
+impl<M: Copy> TranspositionTable<M> {
+    /// A table of the positions of a board of `cells` cells, taking about `bytes` of memory once filled.
+    pub fn new(bytes: usize, cells: usize) -> Self {
+        let slot = size_of::<Option<Entry<M>>>() + cells;
+        TranspositionTable {
+            entries: Vec::new(),
+            tiles: Vec::new(),
+            cells,
+            // Whole buckets of two slots, at least one
+            slots: (bytes / slot / 2).max(1) * 2,
+            generation: 0,
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// The score of the position and its best move, if it was searched at least `depth` moves deep.
+    pub fn get(&mut self, key: u64, tiles: &[u8], depth: usize) -> Option<(f64, Option<M>, Bound)> {
+        let found = self.bucket(key).find(|&slot| self.holds(slot, key, tiles));
+        match found.and_then(|slot| self.entries[slot]) {
+            Some(entry) if entry.depth as usize >= depth => {
+                self.stats.hits += 1;
+                Some((entry.score, entry.best_move, entry.bound))
+            }
+            entry => {
+                self.stats.misses += 1;
+                self.stats.shallow += entry.is_some() as u64;
+                None
+            }
+        }
+    }
+
+    /// Keeps the score of the position searched `depth` moves deep, unless its slot holds a better one.
+    pub fn insert(&mut self, key: u64, tiles: &[u8], depth: usize, bound: Bound, score: f64, best_move: Option<M>) {
+        if self.entries.is_empty() {
+            self.entries = vec![None; self.slots];
+            self.tiles = vec![0; self.slots * self.cells];
+        }
+        let entry = Entry {
+            key,
+            score,
+            best_move,
+            depth: depth.min(u8::MAX as usize) as u8,
+            bound,
+            generation: self.generation,
+        };
+        let slot = match self.bucket(key).find(|&slot| self.holds(slot, key, tiles)) {
+            // The same position searched deeper, or as deep and exactly, stays
+            Some(slot) => match self.entries[slot] {
+                Some(kept) if (kept.depth, kept.bound) > (entry.depth, entry.bound) => return,
+                _ => slot,
+            },
+            None => {
+                let slot = self.bucket(key).min_by_key(|&slot| self.worth(slot)).expect("a bucket has two slots");
+                self.stats.evictions += self.entries[slot].is_some() as u64;
+                slot
+            }
+        };
+        self.stats.stores += 1;
+        self.entries[slot] = Some(entry);
+        self.tiles[slot * self.cells..(slot + 1) * self.cells].copy_from_slice(tiles);
+    }
+
+    /// Starts the search of a new move: the scores stored until now give way to the new ones.
+    pub fn new_move(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+    }
+
+    /// Forgets every position and the statistics, keeping the memory.
+    pub fn clear(&mut self) {
+        self.entries.fill(None);
+        self.generation = 0;
+        self.stats = CacheStats::default();
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// The number of positions the table can hold.
+    pub fn capacity(&self) -> usize {
+        self.slots
+    }
+
+    /// The number of positions it holds.
+    pub fn len(&self) -> usize {
+        self.entries.iter().filter(|entry| entry.is_some()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // The two slots a key can be stored in
+    fn bucket(&self, key: u64) -> std::ops::Range<usize> {
+        let first = (key % (self.slots / 2) as u64) as usize * 2;
+        first..first + 2
+    }
+
+    fn holds(&self, slot: usize, key: u64, tiles: &[u8]) -> bool {
+        match self.entries.get(slot).copied().flatten() {
+            Some(entry) => entry.key == key && self.tiles[slot * self.cells..(slot + 1) * self.cells] == *tiles,
+            None => false,
+        }
+    }
+
+    // How much the slot is worth keeping: empty first, then the earlier moves, then the shallow and the estimated scores
+    fn worth(&self, slot: usize) -> (bool, bool, u8, Bound) {
+        match self.entries[slot] {
+            Some(entry) => (true, entry.generation == self.generation, entry.depth, entry.bound),
+            None => (false, false, 0, Bound::Estimate),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn earlier_moves_give_way_after_any_number_of_moves() {
+        // A single bucket, the tiles telling the positions apart
+        let mut table = TranspositionTable::<()>::new(0, 1);
+        table.insert(0, &[1], 3, Bound::Exact, 1.0, None);
+        for _ in 0..256 {
+            table.new_move();
+        }
+        table.insert(0, &[2], 1, Bound::Exact, 2.0, None);
+        // The deep score of 256 moves ago goes before the shallow current one
+        table.insert(0, &[3], 1, Bound::Exact, 3.0, None);
+        assert_eq!(table.get(0, &[1], 0), None);
+        assert_eq!(table.get(0, &[2], 1), Some((2.0, None, Bound::Exact)));
+        assert_eq!(table.get(0, &[3], 1), Some((3.0, None, Bound::Exact)));
+        assert_eq!(table.stats().evictions, 1);
+    }
+
+    #[test]
+    fn shallow_scores_are_misses() {
+        let mut table = TranspositionTable::<u8>::new(1 << 10, 2);
+        table.insert(7, &[1, 2], 2, Bound::Exact, 5.0, Some(1));
+        assert_eq!(table.get(7, &[1, 2], 3), None);
+        assert_eq!(table.get(7, &[1, 2], 2), Some((5.0, Some(1), Bound::Exact)));
+        // A score searched deeper is as good
+        assert_eq!(table.get(7, &[1, 2], 1), Some((5.0, Some(1), Bound::Exact)));
+        assert_eq!((table.stats().hits, table.stats().misses, table.stats().shallow), (2, 1, 1));
+        // Nor does a shallower search replace it
+        table.insert(7, &[1, 2], 1, Bound::Exact, 6.0, Some(2));
+        assert_eq!(table.get(7, &[1, 2], 2), Some((5.0, Some(1), Bound::Exact)));
+    }
+
+    #[test]
+    fn positions_sharing_a_key_are_told_apart() {
+        let mut table = TranspositionTable::<u8>::new(1 << 10, 2);
+        table.insert(7, &[1, 2], 1, Bound::Exact, 5.0, Some(1));
+        assert_eq!(table.get(7, &[2, 1], 1), None);
+        assert_eq!(table.stats().shallow, 0);
+        table.insert(7, &[2, 1], 1, Bound::Estimate, 6.0, Some(2));
+        assert_eq!(table.get(7, &[1, 2], 1), Some((5.0, Some(1), Bound::Exact)));
+        assert_eq!(table.get(7, &[2, 1], 1), Some((6.0, Some(2), Bound::Estimate)));
+        assert_eq!((table.len(), table.stats().evictions), (2, 0));
+    }
+
+    #[test]
+    fn clear_forgets_the_positions_and_the_statistics() {
+        let mut table = TranspositionTable::<u8>::new(1 << 10, 2);
+        table.insert(7, &[1, 2], 1, Bound::Exact, 5.0, None);
+        table.get(7, &[1, 2], 1);
+        table.new_move();
+        let capacity = table.capacity();
+        table.clear();
+        assert!(table.is_empty());
+        assert_eq!(table.stats(), CacheStats::default());
+        assert_eq!(table.capacity(), capacity);
+        assert_eq!(table.get(7, &[1, 2], 0), None);
+    }
+}
@@ -0,0 +1,173 @@
+use crate::engine::{AxisMove, Cells, Engine, Geometry};
+use crate::state::GameState;
+use crate::zobrist;
+use strum::IntoEnumIterator;
+
+/// A rotation or a reflection of a board of `D` axes: axis `i` of the new board is axis `axes[i]` of the old one,
+/// read backwards when `flipped[i]` is set. A square board has 8 of them, a cube 48, 24 of which are rotations.
+///
+/// ```
+/// use game_2048::game::{Direction, Game, Plane};
+/// use game_2048::notation::Notation;
+/// use game_2048::rules::Rules;
+/// use game_2048::state::GameState;
+///
+/// let game = Game::<2>::from_notation(&"2x2:0,3/0,1".parse::<Notation>().unwrap(), 0, Rules::default()).unwrap();
+/// let (canonical, symmetry) = game.canonical();
+/// assert_eq!(canonical.notation().to_string(), "2x2:0,0/1,3 player 0");
+/// // The rows of the canonical board are the columns of the game
+/// assert_eq!(symmetry.move_back::<Plane<2, 2>>(Direction::Down), Direction::Right);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Symmetry<const D: usize> {
+    pub axes: [usize; D],
+    pub flipped: [bool; D],
+}
+
+impl<const D: usize> Symmetry<D> {
+    pub fn identity() -> Self {
+        Symmetry {
+            axes: std::array::from_fn(|axis| axis),
+            flipped: [false; D],
+        }
+    }
+
+    /// Every rotation and reflection, the identity first: the orders of the axes, each with every set of flipped axes.
+    pub fn all() -> Vec<Self> {
+        // The orders of the axes, built one axis at a time
+        let mut orders: Vec<Vec<usize>> = vec![Vec::new()];
+        for _ in 0..D {
+            let mut longer = Vec::new();
+            for order in &orders {
+                for axis in (0..D).filter(|axis| !order.contains(axis)) {
+                    longer.push([order.as_slice(), &[axis]].concat());
+                }
+            }
+            orders = longer;
+        }
+        orders
+            .into_iter()
+            .flat_map(|order| {
+                (0..1usize << D).map(move |mask| Symmetry {
+                    axes: std::array::from_fn(|i| order[i]),
+                    flipped: std::array::from_fn(|i| mask >> i & 1 == 1),
+                })
+            })
+            .collect()
+    }
+
+    /// The symmetries that turn the board rather than mirror it.
+    pub fn rotations() -> Vec<Self> {
+        Self::all().into_iter().filter(|symmetry| symmetry.is_rotation()).collect()
+    }
+
+    /// Whether the board is turned: an even number of swapped axes and flipped ones together.
+    pub fn is_rotation(&self) -> bool {
+        let swaps = (0..D).flat_map(|i| (i + 1..D).map(move |j| (i, j))).filter(|&(i, j)| self.axes[i] > self.axes[j]).count();
+        let flips = self.flipped.iter().filter(|&&flipped| flipped).count();
+        (swaps + flips).is_multiple_of(2)
+    }
+
+    /// Whether a board of `shape` keeps its shape, only the axes of the same length being swapped.
+    pub fn preserves(&self, shape: &[usize]) -> bool {
+        shape.len() == D && (0..D).all(|i| shape[self.axes[i]] == shape[i])
+    }
+
+    /// The symmetry undoing this one.
+    pub fn inverse(&self) -> Self {
+        let mut inverse = Self::identity();
+        for i in 0..D {
+            inverse.axes[self.axes[i]] = i;
+            inverse.flipped[self.axes[i]] = self.flipped[i];
+        }
+        inverse
+    }
+
+    /// Where the tile in `cell` of a board of `shape` goes, the last axis varying fastest.
+    pub fn cell(&self, cell: usize, shape: &[usize]) -> usize {
+        let mut coordinates = [0; D];
+        let mut rest = cell;
+        for axis in (0..D).rev() {
+            coordinates[axis] = rest % shape[axis];
+            rest /= shape[axis];
+        }
+        (0..D).fold(0, |new, i| {
+            let t = coordinates[self.axes[i]];
+            new * shape[i] + if self.flipped[i] { shape[i] - 1 - t } else { t }
+        })
+    }
+
+    /// The tiles of a board of `shape` once transformed.
+    pub fn apply(&self, tiles: &[u8], shape: &[usize]) -> Vec<u8> {
+        let mut transformed = vec![0; tiles.len()];
+        for (cell, &tile) in tiles.iter().enumerate() {
+            transformed[self.cell(cell, shape)] = tile;
+        }
+        transformed
+    }
+
+    /// The move of the transformed board that slides its tiles as `direction` slides the original ones.
+    pub fn forward(&self, direction: AxisMove) -> AxisMove {
+        let axis = self.axes.iter().position(|&axis| axis == direction.axis).expect("the move is along an axis of the board");
+        AxisMove {
+            axis,
+            towards_start: direction.towards_start != self.flipped[axis],
+        }
+    }
+
+    /// The move of the original board that slides its tiles as `direction` slides the transformed ones.
+    pub fn back(&self, direction: AxisMove) -> AxisMove {
+        AxisMove {
+            axis: self.axes[direction.axis],
+            towards_start: direction.towards_start != self.flipped[direction.axis],
+        }
+    }
+
+    /// `forward` with the moves of a geometry, such as the best move of the original board in a table of transformed ones.
+    pub fn move_forward<G: Geometry>(&self, direction: G::Move) -> G::Move {
+        named::<G>(self.forward(G::axis_move(direction)))
+    }
+
+    /// `back` with the moves of a geometry, such as the best move of a transformed board played on the original one.
+    pub fn move_back<G: Geometry>(&self, direction: G::Move) -> G::Move {
+        named::<G>(self.back(G::axis_move(direction)))
+    }
+}
+
+impl<const D: usize> Default for Symmetry<D> {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+// The move of the geometry sliding along the axis move
+fn named<G: Geometry>(direction: AxisMove) -> G::Move {
+    G::Move::iter().find(|&named| G::axis_move(named) == direction).expect("every axis move has a name")
+}
+
+impl<G: Geometry> Engine<G> {
+    /// The game with its board rotated or mirrored, the score and the rest unchanged.
+    /// The history is not carried over, its boards aren't transformed.
+    /// Panics when the symmetry doesn't keep the shape of the board, see `Symmetry::preserves`.
+    pub fn transformed<const D: usize>(&self, symmetry: &Symmetry<D>) -> Self {
+        assert!(symmetry.preserves(G::SHAPE), "{:?} doesn't keep the shape of a {:?} board", symmetry, G::SHAPE);
+        let mut game = Engine { history: None, ..self.clone() };
+        game.data.tiles_mut().copy_from_slice(&symmetry.apply(self.data(), G::SHAPE));
+        game.hash = zobrist::hash(game.data.tiles());
+        game
+    }
+
+    /// The transform of the game among `symmetries` with the smallest tiles, cell by cell, and the symmetry that makes it.
+    /// The games that are transforms of each other have the same canonical form, as long as `symmetries` is a group.
+    /// The first of `symmetries` wins a tie, and `symmetry.move_back` turns the moves of the canonical game into moves of this one.
+    pub fn canonical_among<const D: usize>(&self, symmetries: &[Symmetry<D>]) -> (Self, Symmetry<D>) {
+        let best = symmetries
+            .iter()
+            .filter(|symmetry| symmetry.preserves(G::SHAPE))
+            .map(|symmetry| (symmetry.apply(self.data(), G::SHAPE), symmetry))
+            .min_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(_, &symmetry)| symmetry)
+            .expect("the symmetries keep the shape of the board");
+        (self.transformed(&best), best)
+    }
+}
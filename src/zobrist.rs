@@ -0,0 +1,79 @@
+/// The Zobrist key of `tile` in `cell`. The hash of a board is the xor of the keys of its tiles,
+/// so a move or a spawn updates it with the keys of the cells it changed, see `GameState::zobrist`.
+/// Empty cells have no key. The keys are drawn from the cell and the tile with splitmix64 rather than
+/// kept in a table, the 4D boards would need megabytes of them.
+pub fn key(cell: usize, tile: u8) -> u64 {
+    if tile == 0 {
+        return 0;
+    }
+    let mut z = ((cell as u64) << 8 | tile as u64).wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// The Zobrist hash of a whole board, the tiles with the last axis varying fastest.
+pub fn hash(tiles: &[u8]) -> u64 {
+    tiles.iter().enumerate().fold(0, |hash, (cell, &tile)| hash ^ key(cell, tile))
+}
+
+/// Writes `tile` in `cell`, keeping the hash of the board up to date.
+pub fn set(tiles: &mut [u8], hash: &mut u64, cell: usize, tile: u8) {
+    *hash ^= key(cell, tiles[cell]) ^ key(cell, tile);
+    tiles[cell] = tile;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bonus::Bonus;
+    use crate::game::Game;
+    use crate::rules::Rules;
+    use crate::state::GameState;
+    use crate::three_d_game::ThreeDGame;
+    use rand::{Rng, SeedableRng};
+    use rand_xoshiro::Xoshiro256Plus;
+
+    // Plays random moves, undoing one in four, checking the incremental hash against the whole board's after every step
+    fn check<G: GameState>(mut game: G, undo: impl Fn(&mut G) -> bool) {
+        let mut rng = Xoshiro256Plus::seed_from_u64(5);
+        let mut undos = 0;
+        for _ in 0..300 {
+            if rng.gen_bool(0.25) {
+                undos += undo(&mut game) as usize;
+            } else {
+                let moves: Vec<G::Move> = game.legal_moves().iter().collect();
+                if moves.is_empty() {
+                    break;
+                }
+                game.play_turn(moves[rng.gen_range(0..moves.len())]);
+            }
+            assert_eq!(game.zobrist(), hash(game.data()));
+        }
+        assert!(undos > 0);
+    }
+
+    #[test]
+    fn moves_spawns_and_undos_keep_the_hash() {
+        // The packed moves of the 4x4 board and the generic ones of the others
+        check(Game::<4>::with_seed(1).with_history(10), Game::undo);
+        check(Game::<3, 5>::with_seed(2).with_history(10), Game::undo);
+        check(ThreeDGame::<3>::with_seed(3).with_history(10), ThreeDGame::undo);
+        // The bonused tiles leaving the board
+        let bonus = Game::<4>::with_rules(4, Rules::default().with_bonus(Bonus::web()));
+        check(bonus.with_history(10), Game::undo);
+    }
+
+    #[test]
+    fn setting_a_cell_updates_the_hash() {
+        let mut tiles = [0, 3, 0, 1];
+        let mut board = hash(&tiles);
+        set(&mut tiles, &mut board, 0, 2);
+        set(&mut tiles, &mut board, 1, 0);
+        set(&mut tiles, &mut board, 3, 1);
+        assert_eq!(tiles, [2, 0, 0, 1]);
+        assert_eq!(board, hash(&tiles));
+        assert_eq!(key(2, 0), 0);
+        assert_ne!(key(0, 1), key(1, 1));
+    }
+}